@@ -3,7 +3,7 @@ use figment::{
     Figment,
 };
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use validator::Validate;
 
 #[derive(Debug, Clone, Validate, Deserialize, Serialize)]
@@ -11,16 +11,268 @@ pub struct Settings {
     #[serde(default)]
     pub server: ServerConfig,
 
+    #[serde(default)]
+    pub ip_database_backend: IpDatabaseBackend,
+
+    /// CSV feed or compiled snapshot; the format is detected on load.
     #[validate(length(min = 1))]
     #[serde(default = "default_ip_database_path")]
     pub ip_database_path: String,
 
+    /// Skip malformed rows in the CSV feed instead of refusing to start.
+    #[serde(default)]
+    pub ip_database_lenient: bool,
+
+    /// Additional feeds merged after `ip_database_path`.
+    #[serde(default)]
+    #[validate(nested)]
+    pub sources: Vec<SourceConfig>,
+
+    #[serde(default)]
+    pub mmdb: MmdbConfig,
+
+    /// Feeds kept in sync with a signed HTTP(S) mirror.
+    #[serde(default)]
+    #[validate(nested)]
+    pub feed_updates: FeedUpdatesConfig,
+
+    /// `asn,organization,type` CSV classifying ASNs as hosting, isp, mobile,
+    /// education or vpn_provider.
+    #[serde(default)]
+    pub asn_table_path: Option<String>,
+
+    /// Routeviews-style `prefix<TAB>length<TAB>asn` dump used to find the
+    /// ASN announcing an address.
+    #[serde(default)]
+    pub prefix_to_asn_path: Option<String>,
+
+    /// Operator `cidr,verdict,reason,expires` CSV that overrides every signal.
+    #[serde(default)]
+    pub overrides_path: Option<String>,
+
+    /// Tor bulk exit list or `exit-addresses` file merged as `tor` ranges.
+    #[serde(default)]
+    pub tor_exit_list_path: Option<String>,
+
+    /// Tor exits stop matching once published longer ago than this, without
+    /// waiting for a reload; `0` keeps all.
+    #[serde(default = "default_tor_exit_max_age")]
+    pub tor_exit_max_age_hours: u64,
+
+    /// How often to check `ip_database_path` for changes; `0` disables the
+    /// watcher and leaves SIGHUP as the only reload trigger. A change is
+    /// loaded once the files have held still for a whole interval.
+    #[serde(default = "default_reload_interval")]
+    pub ip_database_reload_interval_sec: u64,
+
+    /// Addresses whose range lookup, hit or miss, is remembered.
+    #[serde(default = "default_ip_cache_capacity")]
+    #[validate(range(min = 1))]
+    pub ip_cache_capacity: usize,
+
+    /// How often to log the lookup cache's hit, miss and eviction counts;
+    /// `0` disables the log.
+    #[serde(default = "default_ip_cache_stats_interval")]
+    pub ip_cache_stats_interval_sec: u64,
+
     #[serde(default = "default_threshold")]
     #[validate(range(min = 0.1, max = 1.0))]
     pub vpn_threshold: f32,
 
+    #[serde(default)]
+    pub category_weights: CategoryWeightsConfig,
+
+    #[serde(default)]
+    pub asn_type_weights: AsnTypeWeightsConfig,
+
+    /// IP database evidence halves in weight every this many hours since a
+    /// feed last saw the range; `0` disables decay.
+    #[serde(default = "default_score_half_life")]
+    pub score_half_life_hours: u64,
+
     #[serde(default = "default_dns_timeout")]
     pub dns_timeout_sec: u64,
+
+    /// TOML file of glob or regex rules, each with a category and weight,
+    /// for PTR names and for the reverse zone's name servers and SOA names;
+    /// without it only PTR names mentioning `vpn` or ending in `.vps` score.
+    #[serde(default)]
+    pub ptr_rules_path: Option<String>,
+
+    #[serde(default)]
+    pub fcrdns_weights: FcrdnsWeightsConfig,
+
+    /// DNSBL zones every address is looked up in.
+    #[serde(default)]
+    #[validate(nested)]
+    pub dnsbl_zones: Vec<DnsblZoneConfig>,
+
+    /// Resolver for DNSBL queries, e.g. `127.0.0.1:53`; the default public
+    /// resolver is refused by blocklists such as Spamhaus.
+    #[serde(default)]
+    pub dnsbl_resolver: Option<SocketAddr>,
+
+    #[serde(default)]
+    #[validate(nested)]
+    pub dns_leak: DnsLeakConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpDatabaseBackend {
+    /// `cidr,asn,provider` feed or compiled `geo-ip` snapshot at
+    /// `ip_database_path`, either optionally gzip or zstd compressed; the
+    /// format is detected from the file.
+    #[default]
+    Csv,
+    /// Local MaxMind databases listed under `[mmdb]`.
+    Mmdb,
+}
+
+/// One `[[sources]]` feed.
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct SourceConfig {
+    #[validate(length(min = 1))]
+    pub path: String,
+
+    #[serde(default)]
+    pub format: SourceFormat,
+
+    /// Category for entries that do not name one, e.g. `"tor"` or `"proxy"`.
+    #[serde(default = "default_source_category")]
+    pub category: String,
+
+    /// How far the feed is trusted, from `0.0` to `1.0`.
+    #[serde(default = "default_source_weight")]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceFormat {
+    /// `cidr,asn,provider[,category]` CSV.
+    #[default]
+    Csv,
+    /// One network or address per line.
+    Cidr,
+    /// One `start-end` range per line.
+    Range,
+    /// `ipset save` output.
+    Ipset,
+    /// `nft list set` output.
+    Nftables,
+}
+
+/// `[feed_updates]`: local feed files refreshed from a mirror. Each download
+/// must carry a detached ed25519 signature.
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FeedUpdatesConfig {
+    /// How often to poll the mirror; `0` updates only at startup.
+    pub interval_sec: u64,
+    /// Base64 ed25519 public key the feeds are signed with.
+    pub public_key: Option<String>,
+    /// PEM CA certificates to trust besides the web PKI, for a mirror with
+    /// an internal CA.
+    pub ca_path: Option<String>,
+    #[validate(range(min = 1))]
+    pub timeout_sec: u64,
+    #[validate(nested)]
+    pub feeds: Vec<FeedUpdateConfig>,
+}
+
+/// One `[[dnsbl_zones]]` blocklist and its return-code table.
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct DnsblZoneConfig {
+    #[validate(length(min = 1))]
+    pub zone: String,
+    /// Return codes not listed here are reported as `listed` without
+    /// adding score.
+    #[serde(default)]
+    #[validate(nested)]
+    pub codes: Vec<DnsblCodeConfig>,
+}
+
+/// One `[[dnsbl_zones.codes]]` entry.
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct DnsblCodeConfig {
+    /// A record the zone returns, e.g. `127.0.0.4`.
+    pub code: Ipv4Addr,
+    #[validate(length(min = 1))]
+    pub category: String,
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub weight: f32,
+}
+
+/// `[dns_leak]`: authoritative name server for per-session probe names,
+/// recording which resolver looks each one up. Off unless `zone` is set.
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DnsLeakConfig {
+    /// Zone delegated to this server, e.g. `probe.example`.
+    pub zone: Option<String>,
+    /// UDP address the name server listens on.
+    pub listen: SocketAddr,
+    /// Addresses probe names resolve to, e.g. the web server embedding them.
+    pub answer_ipv4: Option<Ipv4Addr>,
+    pub answer_ipv6: Option<Ipv6Addr>,
+    /// How long an issued probe name is answered and its resolvers kept.
+    #[validate(range(min = 1))]
+    pub token_ttl_sec: u64,
+}
+
+/// One `[[feed_updates.feeds]]` entry.
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct FeedUpdateConfig {
+    #[validate(length(min = 1))]
+    pub url: String,
+    /// Defaults to `url` with `.sig` appended.
+    #[serde(default)]
+    pub signature_url: Option<String>,
+    /// Local copy; point `ip_database_path` or a `[[sources]]` path at it.
+    #[validate(length(min = 1))]
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MmdbConfig {
+    pub anonymous_ip_path: Option<String>,
+    pub asn_path: Option<String>,
+    /// GeoLite2-City (or Country). With the CSV backend it is still used to
+    /// locate addresses whose range carries no location.
+    pub city_path: Option<String>,
+}
+
+/// Score contributed by an IP database hit, per anonymizer category.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CategoryWeightsConfig {
+    pub vpn: f32,
+    pub tor: f32,
+    pub proxy: f32,
+    pub residential_proxy: f32,
+    pub hosting: f32,
+}
+
+/// Score contributed by the ASN announcing an address, per ASN type.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AsnTypeWeightsConfig {
+    pub hosting: f32,
+    pub isp: f32,
+    pub mobile: f32,
+    pub education: f32,
+    pub vpn_provider: f32,
+}
+
+/// Score for an address whose PTR names do not resolve back to it, or that
+/// has no PTR record at all.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FcrdnsWeightsConfig {
+    pub mismatch: f32,
+    pub no_ptr: f32,
 }
 
 #[derive(Debug, Clone, Validate, Deserialize, Serialize)]
@@ -37,9 +289,87 @@ impl Default for Settings {
     fn default() -> Self {
         Self {
             server: ServerConfig::default(),
+            ip_database_backend: IpDatabaseBackend::default(),
             ip_database_path: default_ip_database_path(),
+            ip_database_lenient: false,
+            sources: Vec::new(),
+            mmdb: MmdbConfig::default(),
+            feed_updates: FeedUpdatesConfig::default(),
+            asn_table_path: None,
+            prefix_to_asn_path: None,
+            overrides_path: None,
+            tor_exit_list_path: None,
+            tor_exit_max_age_hours: default_tor_exit_max_age(),
+            ip_database_reload_interval_sec: default_reload_interval(),
+            ip_cache_capacity: default_ip_cache_capacity(),
+            ip_cache_stats_interval_sec: default_ip_cache_stats_interval(),
             vpn_threshold: default_threshold(),
+            category_weights: CategoryWeightsConfig::default(),
+            asn_type_weights: AsnTypeWeightsConfig::default(),
+            score_half_life_hours: default_score_half_life(),
             dns_timeout_sec: default_dns_timeout(),
+            ptr_rules_path: None,
+            fcrdns_weights: FcrdnsWeightsConfig::default(),
+            dnsbl_zones: Vec::new(),
+            dnsbl_resolver: None,
+            dns_leak: DnsLeakConfig::default(),
+        }
+    }
+}
+
+impl Default for FeedUpdatesConfig {
+    fn default() -> Self {
+        Self {
+            interval_sec: 3600,
+            public_key: None,
+            ca_path: None,
+            timeout_sec: 60,
+            feeds: Vec::new(),
+        }
+    }
+}
+
+impl Default for DnsLeakConfig {
+    fn default() -> Self {
+        Self {
+            zone: None,
+            listen: SocketAddr::from(([0, 0, 0, 0], 53)),
+            answer_ipv4: None,
+            answer_ipv6: None,
+            token_ttl_sec: 300,
+        }
+    }
+}
+
+impl Default for CategoryWeightsConfig {
+    fn default() -> Self {
+        Self {
+            vpn: 0.7,
+            tor: 0.8,
+            proxy: 0.6,
+            residential_proxy: 0.5,
+            hosting: 0.3,
+        }
+    }
+}
+
+impl Default for AsnTypeWeightsConfig {
+    fn default() -> Self {
+        Self {
+            hosting: 0.3,
+            isp: 0.0,
+            mobile: 0.0,
+            education: 0.0,
+            vpn_provider: 0.6,
+        }
+    }
+}
+
+impl Default for FcrdnsWeightsConfig {
+    fn default() -> Self {
+        Self {
+            mismatch: 0.2,
+            no_ptr: 0.1,
         }
     }
 }
@@ -73,12 +403,45 @@ fn default_ip_database_path() -> String {
     "assets/vpn_ips.csv".into()
 }
 
+fn default_source_category() -> String {
+    "vpn".into()
+}
+
+fn default_source_weight() -> f32 {
+    1.0
+}
+
+fn default_tor_exit_max_age() -> u64 {
+    48
+}
+
+fn default_ip_cache_capacity() -> usize {
+    1000
+}
+
+fn default_ip_cache_stats_interval() -> u64 {
+    300
+}
+
+fn default_score_half_life() -> u64 {
+    24 * 30
+}
+
+fn default_reload_interval() -> u64 {
+    60
+}
+
 impl Settings {
+    /// Reads `config/default.toml` and `VPN_*` environment overrides and
+    /// checks the result against the field constraints.
     pub fn load() -> Result<Self, figment::Error> {
-        let config = Figment::new()
+        let config: Self = Figment::new()
             .merge(Toml::file("config/default.toml"))
             .merge(Env::prefixed("VPN_"))
             .extract()?;
+        config
+            .validate()
+            .map_err(|e| figment::Error::from(format!("invalid settings: {}", e)))?;
 
         Ok(config)
     }
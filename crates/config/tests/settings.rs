@@ -0,0 +1,42 @@
+use config::Settings;
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use std::path::Path;
+
+#[test]
+fn out_of_range_settings_are_rejected_on_load() {
+    // The TOML file is looked up relative to the working directory, so only
+    // the built-in defaults and the environment apply here.
+    let settings = Settings::load().unwrap();
+    assert_eq!(settings.ip_cache_capacity, 1000);
+
+    std::env::set_var("VPN_IP_CACHE_CAPACITY", "0");
+    let err = Settings::load().unwrap_err();
+    std::env::remove_var("VPN_IP_CACHE_CAPACITY");
+    assert!(err.to_string().contains("ip_cache_capacity"), "{}", err);
+}
+
+fn shipped(path: &str) -> Figment {
+    Figment::new().merge(Toml::file(Path::new(env!("CARGO_MANIFEST_DIR")).join(path)))
+}
+
+#[test]
+fn shipped_defaults_set_top_level_keys_outside_server() {
+    for path in ["../../config/default.toml", "src/default.toml"] {
+        let figment = shipped(path);
+        // Most shipped values equal the built-in defaults, so a key that
+        // lands in the wrong table is only visible in where it lands.
+        let server = figment.find_value("server").unwrap();
+        let keys: Vec<_> = server.as_dict().unwrap().keys().cloned().collect();
+        assert_eq!(keys, ["host", "port"], "{}", path);
+        let interval = figment.extract_inner::<u64>("ip_database_reload_interval_sec");
+        assert_eq!(interval.unwrap(), 60, "{}", path);
+
+        let settings: Settings = figment.extract().unwrap();
+        assert_eq!(settings.ip_database_reload_interval_sec, 60);
+        assert_eq!(settings.score_half_life_hours, 720);
+    }
+
+    let settings: Settings = shipped("../../config/default.toml").extract().unwrap();
+    assert_eq!(settings.server.port, 50550);
+}
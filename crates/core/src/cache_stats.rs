@@ -0,0 +1,32 @@
+use detector::VpnDetectorImpl;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{interval, MissedTickBehavior};
+
+/// Logs the IP lookup cache counters every `period`; a zero period disables
+/// the log. Counters restart whenever the IP database is reloaded.
+pub fn spawn(detector: Arc<VpnDetectorImpl>, period: Duration) {
+    if period.is_zero() {
+        return;
+    }
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    tokio::spawn(async move {
+        // The first tick completes immediately, before any lookups.
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let stats = detector.cache_stats();
+            println!(
+                "IP lookup cache: {} hits, {} misses ({:.1}% hit rate), {} evictions, {}/{} entries",
+                stats.hits,
+                stats.misses,
+                stats.hit_ratio() * 100.0,
+                stats.evictions,
+                stats.len,
+                stats.capacity
+            );
+        }
+    });
+}
@@ -0,0 +1,144 @@
+use config::{IpDatabaseBackend, Settings, SourceConfig, SourceFormat};
+use geo_ip::{
+    Category, GeoIpError, IpDatabase, ListFormat, LoadMode, LoadReport, MmdbPaths, SourceOptions,
+};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Loads the IP database from whichever backend `settings` selects (with the
+/// CSV backend, `mmdb.city_path` only locates addresses), then merges the
+/// extra sources, the Tor exit list, the ASN tables and operator overrides if
+/// they are configured.
+pub fn load(settings: &Settings) -> Result<IpDatabase, GeoIpError> {
+    let mode = if settings.ip_database_lenient {
+        LoadMode::Lenient
+    } else {
+        LoadMode::Strict
+    };
+    let mut ip_db = match settings.ip_database_backend {
+        IpDatabaseBackend::Csv => {
+            let (mut ip_db, report) = IpDatabase::load_from_file(&settings.ip_database_path, mode)?;
+            log_report(&settings.ip_database_path, &report);
+            if let Some(path) = &settings.mmdb.city_path {
+                ip_db.load_geo_mmdb(path)?;
+            }
+            ip_db
+        }
+        IpDatabaseBackend::Mmdb => IpDatabase::load_from_mmdb(&mmdb_paths(settings))?,
+    };
+
+    for source in &settings.sources {
+        let report = merge_source(&mut ip_db, source, mode)?;
+        log_report(&source.path, &report);
+    }
+
+    if let Some(path) = &settings.tor_exit_list_path {
+        let max_age = (settings.tor_exit_max_age_hours > 0)
+            .then(|| Duration::from_secs(settings.tor_exit_max_age_hours * 3600));
+        ip_db.merge_tor_exit_list(path, max_age)?;
+    }
+    if let Some(path) = &settings.asn_table_path {
+        ip_db.load_asn_table(path)?;
+    }
+    if let Some(path) = &settings.prefix_to_asn_path {
+        ip_db.load_prefix_to_asn(path)?;
+    }
+    if let Some(path) = &settings.overrides_path {
+        ip_db.load_overrides(path)?;
+    }
+    if let Some(capacity) = NonZeroUsize::new(settings.ip_cache_capacity) {
+        ip_db.set_cache_capacity(capacity);
+    }
+
+    Ok(ip_db)
+}
+
+fn merge_source(
+    ip_db: &mut IpDatabase,
+    source: &SourceConfig,
+    mode: LoadMode,
+) -> Result<LoadReport, GeoIpError> {
+    let options = SourceOptions {
+        category: source
+            .category
+            .parse::<Category>()
+            .map_err(GeoIpError::InvalidCategory)?,
+        weight: source.weight,
+    };
+    let format = match source.format {
+        SourceFormat::Csv => return ip_db.merge_csv(&source.path, &options, mode),
+        SourceFormat::Cidr => ListFormat::Cidr,
+        SourceFormat::Range => ListFormat::Range,
+        SourceFormat::Ipset => ListFormat::Ipset,
+        SourceFormat::Nftables => ListFormat::Nftables,
+    };
+    ip_db.merge_list(&source.path, format, &options, mode)
+}
+
+fn log_report(path: &str, report: &LoadReport) {
+    println!(
+        "Loaded {}: {} entries, {} skipped, {} duplicates, {} overlapping ranges",
+        path,
+        report.loaded,
+        report.skipped.len(),
+        report.duplicates,
+        report.overlaps.len()
+    );
+    for skipped in &report.skipped {
+        eprintln!("{}:{}: skipped row: {}", path, skipped.row, skipped.reason);
+    }
+    for overlap in &report.overlaps {
+        eprintln!(
+            "{}: warning: {} overlaps {}",
+            path, overlap.cidr, overlap.within
+        );
+    }
+}
+
+/// Files whose modification should trigger a reload.
+pub fn source_paths(settings: &Settings) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match settings.ip_database_backend {
+        IpDatabaseBackend::Csv => [
+            Some(&settings.ip_database_path),
+            settings.mmdb.city_path.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .collect(),
+        IpDatabaseBackend::Mmdb => {
+            let paths = mmdb_paths(settings);
+            [paths.anonymous_ip, paths.asn, paths.city]
+                .into_iter()
+                .flatten()
+                .collect()
+        }
+    };
+    paths.extend(
+        settings
+            .sources
+            .iter()
+            .map(|source| PathBuf::from(&source.path)),
+    );
+    paths.extend(settings.tor_exit_list_path.iter().map(PathBuf::from));
+    paths.extend(
+        [
+            &settings.asn_table_path,
+            &settings.prefix_to_asn_path,
+            &settings.overrides_path,
+        ]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from),
+    );
+    paths
+}
+
+fn mmdb_paths(settings: &Settings) -> MmdbPaths {
+    MmdbPaths {
+        anonymous_ip: settings.mmdb.anonymous_ip_path.as_ref().map(PathBuf::from),
+        asn: settings.mmdb.asn_path.as_ref().map(PathBuf::from),
+        city: settings.mmdb.city_path.as_ref().map(PathBuf::from),
+    }
+}
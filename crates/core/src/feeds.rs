@@ -0,0 +1,81 @@
+use config::Settings;
+use feed_sync::{decode_public_key, Feed, FeedError, FeedUpdater, Update};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{interval, MissedTickBehavior};
+
+/// The `[feed_updates]` feeds and the updater that refreshes them.
+pub struct FeedSync {
+    updater: FeedUpdater,
+    feeds: Vec<Feed>,
+    interval: Duration,
+}
+
+impl FeedSync {
+    /// Returns `None` when no feeds are configured.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, FeedError> {
+        let config = &settings.feed_updates;
+        if config.feeds.is_empty() {
+            return Ok(None);
+        }
+
+        let public_key = decode_public_key(config.public_key.as_deref().unwrap_or_default())?;
+        let mut updater =
+            FeedUpdater::new(public_key).with_timeout(Duration::from_secs(config.timeout_sec));
+        if let Some(path) = &config.ca_path {
+            updater = updater.with_ca_file(path)?;
+        }
+        let feeds = config
+            .feeds
+            .iter()
+            .map(|feed| Feed::new(&feed.url, feed.signature_url.as_deref(), &feed.path))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Some(Self {
+            updater,
+            feeds,
+            interval: Duration::from_secs(config.interval_sec),
+        }))
+    }
+
+    /// Updates every feed once and returns whether any local copy changed.
+    /// A feed that fails to update keeps its last good copy.
+    pub async fn update_all(&self) -> bool {
+        let mut changed = false;
+        for feed in &self.feeds {
+            match self.updater.update(feed).await {
+                Ok(Update::Updated { bytes }) => {
+                    println!("Feed {} updated ({} bytes)", feed.url, bytes);
+                    changed = true;
+                }
+                Ok(Update::Unchanged) => {}
+                Err(e) => eprintln!(
+                    "Failed to update feed {}, keeping last good copy: {}",
+                    feed.url, e
+                ),
+            }
+        }
+        changed
+    }
+
+    /// Polls the mirror every `interval_sec`, notifying `reload` when a feed
+    /// changed. The first poll happens one interval from now.
+    pub fn spawn(self, reload: Arc<Notify>) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let mut ticker = interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        tokio::spawn(async move {
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if self.update_all().await {
+                    reload.notify_one();
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,31 @@
+use config::DnsLeakConfig;
+use dns_leak::{LeakError, LeakServer, LeakTracker};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Starts the leak test name server when a probe zone is configured and
+/// returns the tracker probe tokens are issued from.
+pub async fn spawn(config: &DnsLeakConfig) -> Result<Option<Arc<LeakTracker>>, LeakError> {
+    let Some(zone) = &config.zone else {
+        return Ok(None);
+    };
+    let tracker = Arc::new(LeakTracker::new(
+        zone,
+        Duration::from_secs(config.token_ttl_sec),
+    )?);
+    let server = LeakServer::bind(config.listen, Arc::clone(&tracker))
+        .await?
+        .with_answers(config.answer_ipv4, config.answer_ipv6);
+
+    println!(
+        "DNS leak server for {} listening on {}",
+        zone,
+        server.local_addr()?
+    );
+    tokio::spawn(async move {
+        if let Err(e) = server.run().await {
+            eprintln!("DNS leak server stopped: {}", e);
+        }
+    });
+    Ok(Some(tracker))
+}
@@ -1,14 +1,27 @@
+mod cache_stats;
+mod database;
+mod feeds;
+mod leak_server;
+mod reload;
+
 use config::Settings;
-use detector::{VpnDetector, VpnDetectorImpl};
+use detector::{AsnTypeWeights, CategoryWeights, ResolverCheck, VpnDetector, VpnDetectorImpl};
+use dns_leak::LeakTracker;
 use protobuf_api::vpn_detector::{
     vpn_detector_service_server::{VpnDetectorService, VpnDetectorServiceServer},
-    CheckIpRequest, CheckIpResponse,
+    CheckIpRequest, CheckIpResponse, Coordinates, DnsblListing, IssueLeakProbeRequest, LeakProbe,
+    LeakResolver, Location, OperatorOverride, PtrRuleMatch, ReverseZone,
 };
 use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
 use tonic::{transport::Server, Request, Response, Status};
 
 struct VpnDetectorServiceImpl {
-    detector: VpnDetectorImpl,
+    detector: Arc<VpnDetectorImpl>,
+    /// Present when the DNS leak test is configured.
+    leak_tracker: Option<Arc<LeakTracker>>,
 }
 
 #[tonic::async_trait]
@@ -17,7 +30,7 @@ impl VpnDetectorService for VpnDetectorServiceImpl {
         &self,
         request: Request<CheckIpRequest>,
     ) -> Result<Response<CheckIpResponse>, Status> {
-        let ip = request.into_inner().ip;
+        let CheckIpRequest { ip, leak_token } = request.into_inner();
         let ip_addr: IpAddr = ip
             .parse()
             .map_err(|_| Status::invalid_argument("Invalid IP"))?;
@@ -28,14 +41,136 @@ impl VpnDetectorService for VpnDetectorServiceImpl {
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
 
+        let resolvers = match &self.leak_tracker {
+            Some(tracker) if !leak_token.is_empty() => {
+                tracker.resolvers(&leak_token).unwrap_or_default()
+            }
+            _ => Vec::new(),
+        };
+        let leak_resolvers = self
+            .detector
+            .check_resolvers(ip_addr, &resolvers)
+            .into_iter()
+            .map(leak_resolver)
+            .collect();
+
+        let (matched_address, address_form) = result
+            .details
+            .ip_match
+            .as_ref()
+            .map(|m| (m.address.to_string(), m.form.to_string()))
+            .unwrap_or_default();
         Ok(Response::new(CheckIpResponse {
             ip,
             is_vpn: result.is_vpn,
             score: result.score,
+            operator_override: result.details.operator_override.map(|o| OperatorOverride {
+                cidr: o.cidr.to_string(),
+                verdict: o.verdict.to_string(),
+                reason: o.reason.as_deref().unwrap_or_default().to_string(),
+            }),
+            location: result.details.location.map(|l| Location {
+                country: l.country.as_deref().unwrap_or_default().to_string(),
+                region: l.region.as_deref().unwrap_or_default().to_string(),
+                coordinates: l.coordinates.map(|c| Coordinates {
+                    latitude: c.latitude,
+                    longitude: c.longitude,
+                }),
+            }),
+            matched_address,
+            address_form,
+            fcrdns: result
+                .details
+                .fcrdns
+                .map(|f| f.to_string())
+                .unwrap_or_default(),
+            ptr_rule_matches: rule_matches(result.details.ptr_rule_matches),
+            reverse_zone: result.details.reverse_zone.map(|z| ReverseZone {
+                name: z.name,
+                primary_ns: z.primary_ns,
+                contact: z.contact,
+                name_servers: z.name_servers,
+            }),
+            zone_rule_matches: rule_matches(result.details.zone_rule_matches),
+            leak_resolvers,
+            dnsbl_listings: result
+                .details
+                .dnsbl_listings
+                .into_iter()
+                .map(|l| DnsblListing {
+                    zone: l.zone,
+                    code: l.code.to_string(),
+                    category: l.category.to_string(),
+                    weight: l.weight,
+                    text: l.text,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn issue_leak_probe(
+        &self,
+        _request: Request<IssueLeakProbeRequest>,
+    ) -> Result<Response<LeakProbe>, Status> {
+        let tracker = self
+            .leak_tracker
+            .as_ref()
+            .ok_or_else(|| Status::failed_precondition("DNS leak test is not configured"))?;
+        let probe = tracker.issue();
+        Ok(Response::new(LeakProbe {
+            token: probe.token,
+            hostname: probe.hostname,
         }))
     }
 }
 
+fn dnsbl_zones(config: &Settings) -> Result<Vec<dns_check::DnsblZone>, dns_check::DnsError> {
+    config
+        .dnsbl_zones
+        .iter()
+        .map(|zone| {
+            let dnsbl = dns_check::DnsblZone::new(&zone.zone)?;
+            Ok(zone.codes.iter().fold(dnsbl, |dnsbl, code| {
+                dnsbl.with_code(code.code, &code.category, code.weight)
+            }))
+        })
+        .collect()
+}
+
+fn leak_resolver(check: ResolverCheck) -> LeakResolver {
+    LeakResolver {
+        ip: check.ip.to_string(),
+        provider: check
+            .ip_match
+            .as_ref()
+            .map(|m| m.provider.to_string())
+            .unwrap_or_default(),
+        category: check.category.map(|c| c.to_string()).unwrap_or_default(),
+        asn: check.asn_match.map_or(0, |m| m.asn),
+        country: check
+            .location
+            .and_then(|l| l.country)
+            .as_deref()
+            .unwrap_or_default()
+            .to_string(),
+        same_network: check.same_network,
+        same_country: check.same_country,
+    }
+}
+
+fn rule_matches(matches: Vec<dns_check::RuleMatch>) -> Vec<PtrRuleMatch> {
+    matches
+        .into_iter()
+        .map(|m| PtrRuleMatch {
+            hostname: m.hostname,
+            pattern: m.pattern.to_string(),
+            category: m.category.to_string(),
+            weight: m.weight,
+            contribution: m.contribution,
+        })
+        .collect()
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = Settings::load()?;
@@ -44,10 +179,73 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse()
         .expect("Failed to parse address");
 
-    let ip_db = geo_ip::IpDatabase::load_from_csv(&config.ip_database_path)?;
-    let dns_detector = dns_check::DnsDetector::new(config.dns_timeout_sec);
-    let detector = VpnDetectorImpl::new(ip_db, dns_detector);
-    let service = VpnDetectorServiceImpl { detector };
+    // Fetch fresh feeds before the first load; on failure the last good
+    // copies on disk are used.
+    let feed_sync = feeds::FeedSync::from_settings(&config)?;
+    if let Some(feed_sync) = &feed_sync {
+        feed_sync.update_all().await;
+    }
+
+    let ip_db = database::load(&config)?;
+    let mut dns_detector = dns_check::DnsDetector::new(config.dns_timeout_sec).with_fcrdns_weights(
+        dns_check::FcrdnsWeights {
+            mismatch: config.fcrdns_weights.mismatch,
+            no_ptr: config.fcrdns_weights.no_ptr,
+        },
+    );
+    if !config.dnsbl_zones.is_empty() {
+        dns_detector = dns_detector.with_dnsbl_zones(dnsbl_zones(&config)?);
+    }
+    if let Some(addr) = config.dnsbl_resolver {
+        dns_detector = dns_detector.with_dnsbl_name_server(addr);
+    }
+    if let Some(path) = &config.ptr_rules_path {
+        dns_detector = dns_detector.with_ptr_rules(dns_check::PtrRules::load(path)?);
+    }
+    let weights = &config.category_weights;
+    let asn_weights = &config.asn_type_weights;
+    let detector = Arc::new(
+        VpnDetectorImpl::new(ip_db, dns_detector)
+            .with_category_weights(CategoryWeights {
+                vpn: weights.vpn,
+                tor: weights.tor,
+                proxy: weights.proxy,
+                residential_proxy: weights.residential_proxy,
+                hosting: weights.hosting,
+            })
+            .with_asn_type_weights(AsnTypeWeights {
+                hosting: asn_weights.hosting,
+                isp: asn_weights.isp,
+                mobile: asn_weights.mobile,
+                education: asn_weights.education,
+                vpn_provider: asn_weights.vpn_provider,
+            })
+            .with_half_life(
+                (config.score_half_life_hours > 0)
+                    .then(|| Duration::from_secs(config.score_half_life_hours * 3600)),
+            ),
+    );
+
+    let reload_requests = Arc::new(Notify::new());
+    reload::spawn(
+        Arc::clone(&detector),
+        config.clone(),
+        Arc::clone(&reload_requests),
+    )?;
+    if let Some(feed_sync) = feed_sync {
+        feed_sync.spawn(reload_requests);
+    }
+    cache_stats::spawn(
+        Arc::clone(&detector),
+        Duration::from_secs(config.ip_cache_stats_interval_sec),
+    );
+
+    let leak_tracker = leak_server::spawn(&config.dns_leak).await?;
+
+    let service = VpnDetectorServiceImpl {
+        detector,
+        leak_tracker,
+    };
 
     println!("GRPC Server starting on {}", addr);
 
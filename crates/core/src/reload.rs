@@ -0,0 +1,71 @@
+use crate::database;
+use config::Settings;
+use detector::VpnDetectorImpl;
+use feed_sync::FeedWatcher;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Notify;
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+/// Reloads the IP database on SIGHUP, when `requests` is notified (e.g. by
+/// a feed update) and, when `ip_database_reload_interval_sec` is non-zero,
+/// whenever its source files change and then hold still for one interval.
+pub fn spawn(
+    detector: Arc<VpnDetectorImpl>,
+    settings: Settings,
+    requests: Arc<Notify>,
+) -> std::io::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    let poll_interval = Duration::from_secs(settings.ip_database_reload_interval_sec);
+    let mut ticker = (!poll_interval.is_zero()).then(|| {
+        let mut ticker = interval(poll_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+    let mut watcher = FeedWatcher::new(database::source_paths(&settings));
+    let settings = Arc::new(settings);
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => {
+                    println!("SIGHUP received, reloading IP database");
+                }
+                _ = requests.notified() => {
+                    println!("Feeds updated, reloading IP database");
+                }
+                _ = tick(&mut ticker) => {
+                    if !watcher.poll() {
+                        continue;
+                    }
+                    println!("IP database source changed, reloading");
+                }
+            }
+
+            watcher.mark_loaded();
+            let settings = Arc::clone(&settings);
+            match detector
+                .reload_ip_database(move || database::load(&settings))
+                .await
+            {
+                Ok(()) => println!("IP database reloaded"),
+                Err(e) => eprintln!(
+                    "Failed to reload IP database, keeping previous version: {}",
+                    e
+                ),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+async fn tick(ticker: &mut Option<Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,82 @@
+use crate::VpnDetectorImpl;
+use geo_ip::{AsnMatch, Category, IpDatabase, IpMatch, Location};
+use std::net::IpAddr;
+
+/// A resolver seen looking up a client's DNS leak probe, classified the same
+/// way as client addresses.
+#[derive(Debug, Clone)]
+pub struct ResolverCheck {
+    pub ip: IpAddr,
+    pub ip_match: Option<IpMatch>,
+    pub category: Option<Category>,
+    pub asn_match: Option<AsnMatch>,
+    pub location: Option<Location>,
+    /// Whether the resolver sits in the client's network: the same ASN when
+    /// both are known, otherwise the same /24 or /48.
+    pub same_network: bool,
+    /// Whether resolver and client are located in the same country; `None`
+    /// when either country is unknown.
+    pub same_country: Option<bool>,
+}
+
+impl<D> VpnDetectorImpl<D> {
+    /// Classifies the resolvers a client's leak probe was looked up through
+    /// and compares each with `client`, the address the client connected
+    /// from. A tunnelled client whose DNS is not tunnelled shows up as
+    /// resolvers outside the client's network, often in another country.
+    pub fn check_resolvers(&self, client: IpAddr, resolvers: &[IpAddr]) -> Vec<ResolverCheck> {
+        let ip_db = self.ip_db.load();
+        let now = self.clock.now();
+        let client_asn = ip_db.lookup_asn(client).map(|m| m.asn);
+        let client_country = locate(&ip_db, client, ip_db.lookup_at(client, now).as_ref())
+            .and_then(|location| location.country);
+
+        resolvers
+            .iter()
+            .map(|&ip| {
+                let ip_match = ip_db.lookup_at(ip, now);
+                let asn_match = ip_db.lookup_asn(ip);
+                let location = locate(&ip_db, ip, ip_match.as_ref());
+                let same_network = match (client_asn, asn_match.as_ref()) {
+                    (Some(client_asn), Some(asn_match)) => client_asn == asn_match.asn,
+                    _ => same_prefix(client, ip),
+                };
+                let country = location.as_ref().and_then(|l| l.country.as_ref());
+                let same_country = client_country
+                    .as_ref()
+                    .zip(country)
+                    .map(|(client, resolver)| client.eq_ignore_ascii_case(resolver));
+                ResolverCheck {
+                    ip,
+                    category: ip_match.as_ref().and_then(|m| m.category),
+                    ip_match,
+                    asn_match,
+                    location,
+                    same_network,
+                    same_country,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Location from the matching range, falling back to the geo database.
+pub(crate) fn locate(
+    ip_db: &IpDatabase,
+    ip: IpAddr,
+    ip_match: Option<&IpMatch>,
+) -> Option<Location> {
+    ip_match
+        .map(IpMatch::location)
+        .filter(|location| !location.is_empty())
+        .or_else(|| ip_db.locate(ip))
+}
+
+/// Same /24 for IPv4 or /48 for IPv6, the usual unit of a customer network.
+fn same_prefix(a: IpAddr, b: IpAddr) -> bool {
+    match (a, b) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => a.octets()[..3] == b.octets()[..3],
+        (IpAddr::V6(a), IpAddr::V6(b)) => a.segments()[..3] == b.segments()[..3],
+        _ => false,
+    }
+}
@@ -1,10 +1,20 @@
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
-use dns_check::{DnsAnalyzer, DnsDetector};
-use geo_ip::IpDatabase;
+use dns_check::{DnsAnalyzer, DnsDetector, DnsblListing, Fcrdns, ReverseZone, RuleMatch};
+use geo_ip::{
+    AsnMatch, AsnType, CacheStats, Category, IpDatabase, IpMatch, Location, Override, SourceMatch,
+    Verdict,
+};
 use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
-use tokio::sync::Mutex;
+
+mod clock;
+mod leak;
+
+pub use clock::{Clock, SystemClock};
+pub use leak::ResolverCheck;
 
 #[derive(Error, Debug)]
 pub enum DetectionError {
@@ -12,6 +22,8 @@ pub enum DetectionError {
     IpError(#[from] geo_ip::GeoIpError),
     #[error("DNS check failed: {0}")]
     DnsError(#[from] dns_check::DnsError),
+    #[error("IP database reload task failed: {0}")]
+    ReloadError(#[from] tokio::task::JoinError),
 }
 
 #[async_trait]
@@ -27,42 +39,295 @@ pub struct DetectionResult {
 
 pub struct DetectionDetails {
     pub ip_check: bool,
+    /// Database range that produced `ip_check`, if the address is listed.
+    pub ip_match: Option<IpMatch>,
+    /// Kind of anonymizer the IP database flagged, if any.
+    pub category: Option<Category>,
+    /// Contribution of the IP database to the total score.
+    pub ip_score: f32,
+    /// Number of independent sources that flag the address.
+    pub agreeing_sources: usize,
+    /// Where the address is located, from its range or the geo database.
+    pub location: Option<Location>,
+    /// ASN announcing the address and its classification, if known.
+    pub asn_match: Option<AsnMatch>,
+    /// Contribution of the ASN classification, e.g. a hosting ASN whose
+    /// exact range is not in any feed.
+    pub asn_score: f32,
     pub dns_score: f32,
+    /// PTR hostname rules the address's reverse DNS names matched, each
+    /// with its contribution to `dns_score`.
+    pub ptr_rule_matches: Vec<RuleMatch>,
+    /// Whether the address's PTR names resolve back to it; `None` when DNS
+    /// was not consulted.
+    pub fcrdns: Option<Fcrdns>,
+    /// Reverse zone of the address and whoever runs it, if known.
+    pub reverse_zone: Option<ReverseZone>,
+    /// Zone rules matching the reverse zone's name servers or SOA names.
+    pub zone_rule_matches: Vec<RuleMatch>,
+    /// DNSBL zones listing the address and what their return codes mean.
+    pub dnsbl_listings: Vec<DnsblListing>,
     pub ttl_analysis: Option<bool>,
+    /// Operator override that decided the verdict; other signals are then
+    /// informational only and DNS is not consulted.
+    pub operator_override: Option<Override>,
 }
 
-pub struct VpnDetectorImpl {
-    ip_db: Arc<Mutex<IpDatabase>>,
-    dns_detector: Arc<Mutex<DnsDetector>>,
+/// Score added when the IP database flags an address, per anonymizer kind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CategoryWeights {
+    pub vpn: f32,
+    pub tor: f32,
+    pub proxy: f32,
+    pub residential_proxy: f32,
+    pub hosting: f32,
 }
 
-impl VpnDetectorImpl {
-    pub fn new(ip_db: IpDatabase, dns_detector: DnsDetector) -> Self {
+impl Default for CategoryWeights {
+    fn default() -> Self {
         Self {
-            ip_db: Arc::new(Mutex::new(ip_db)),
-            dns_detector: Arc::new(Mutex::new(dns_detector)),
+            vpn: 0.7,
+            tor: 0.8,
+            proxy: 0.6,
+            residential_proxy: 0.5,
+            hosting: 0.3,
+        }
+    }
+}
+
+impl CategoryWeights {
+    pub fn weight(&self, category: Category) -> f32 {
+        match category {
+            Category::Vpn => self.vpn,
+            Category::Tor => self.tor,
+            Category::Proxy => self.proxy,
+            Category::ResidentialProxy => self.residential_proxy,
+            Category::Hosting => self.hosting,
+        }
+    }
+
+    /// Combines every flagging source as independent evidence: each source
+    /// scores its category weight scaled by its own weight, the entry's
+    /// confidence and its [`age_factor`], and the result is the chance that
+    /// at least one of them is right. A single fully trusted, fresh source
+    /// scores exactly its category weight.
+    pub fn score(&self, ip_match: &IpMatch, now: SystemTime, half_life: Option<Duration>) -> f32 {
+        let miss: f32 = ip_match
+            .sources
+            .iter()
+            .filter_map(|source| {
+                let category = source.category?;
+                Some(
+                    self.weight(category)
+                        * source.weight
+                        * source.confidence
+                        * age_factor(source, now, half_life),
+                )
+            })
+            .map(|score| 1.0 - score.clamp(0.0, 1.0))
+            .product();
+        1.0 - miss
+    }
+}
+
+/// Score for an address announced by a classified ASN, per ASN type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AsnTypeWeights {
+    pub hosting: f32,
+    pub isp: f32,
+    pub mobile: f32,
+    pub education: f32,
+    pub vpn_provider: f32,
+}
+
+impl Default for AsnTypeWeights {
+    fn default() -> Self {
+        Self {
+            hosting: 0.3,
+            isp: 0.0,
+            mobile: 0.0,
+            education: 0.0,
+            vpn_provider: 0.6,
+        }
+    }
+}
+
+impl AsnTypeWeights {
+    pub fn weight(&self, kind: AsnType) -> f32 {
+        match kind {
+            AsnType::Hosting => self.hosting,
+            AsnType::Isp => self.isp,
+            AsnType::Mobile => self.mobile,
+            AsnType::Education => self.education,
+            AsnType::VpnProvider => self.vpn_provider,
+        }
+    }
+}
+
+/// Halves a source's contribution for every `half_life` since it last saw
+/// the range. Entries without a `last_seen` time, or a disabled half-life,
+/// do not decay.
+pub fn age_factor(source: &SourceMatch, now: SystemTime, half_life: Option<Duration>) -> f32 {
+    match (source.last_seen, half_life) {
+        (Some(last_seen), Some(half_life)) if !half_life.is_zero() => {
+            let age = now.duration_since(last_seen).unwrap_or_default();
+            0.5f64.powf(age.as_secs_f64() / half_life.as_secs_f64()) as f32
         }
+        _ => 1.0,
+    }
+}
+
+/// Both backends are shared by reference, so concurrent `check_vpn` calls
+/// run in parallel instead of queueing behind each other.
+pub struct VpnDetectorImpl<D = DnsDetector> {
+    ip_db: ArcSwap<IpDatabase>,
+    dns_detector: Arc<D>,
+    category_weights: CategoryWeights,
+    asn_type_weights: AsnTypeWeights,
+    half_life: Option<Duration>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<D: DnsAnalyzer> VpnDetectorImpl<D> {
+    pub fn new(ip_db: IpDatabase, dns_detector: D) -> Self {
+        Self {
+            ip_db: ArcSwap::from_pointee(ip_db),
+            dns_detector: Arc::new(dns_detector),
+            category_weights: CategoryWeights::default(),
+            asn_type_weights: AsnTypeWeights::default(),
+            half_life: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_category_weights(mut self, category_weights: CategoryWeights) -> Self {
+        self.category_weights = category_weights;
+        self
+    }
+
+    pub fn with_asn_type_weights(mut self, asn_type_weights: AsnTypeWeights) -> Self {
+        self.asn_type_weights = asn_type_weights;
+        self
+    }
+
+    /// Decays IP database evidence by half for every `half_life` since a
+    /// source last saw the range; `None` turns decay off.
+    pub fn with_half_life(mut self, half_life: Option<Duration>) -> Self {
+        self.half_life = half_life;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Lookup cache counters of the database currently serving checks.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.ip_db.load().cache_stats()
+    }
+
+    /// Swaps in a new IP database. Checks already holding the previous one
+    /// finish against it; every later check sees the new one.
+    pub fn replace_ip_database(&self, ip_db: IpDatabase) {
+        self.ip_db.store(Arc::new(ip_db));
+    }
+
+    /// Runs `load` on a blocking thread and swaps in the database it returns.
+    ///
+    /// On error the current database stays in place and keeps serving.
+    pub async fn reload_ip_database<F>(&self, load: F) -> Result<(), DetectionError>
+    where
+        F: FnOnce() -> Result<IpDatabase, geo_ip::GeoIpError> + Send + 'static,
+    {
+        let ip_db = tokio::task::spawn_blocking(load).await??;
+        self.replace_ip_database(ip_db);
+        Ok(())
     }
 }
 
 #[async_trait]
-impl VpnDetector for VpnDetectorImpl {
+impl<D: DnsAnalyzer + Send + Sync> VpnDetector for VpnDetectorImpl<D> {
     async fn check_vpn(&self, ip: IpAddr) -> Result<DetectionResult, DetectionError> {
-        let mut ip_db = self.ip_db.lock().await;
-        let ip_check = ip_db.is_vpn_ip(ip);
+        let now = self.clock.now();
+        let (ip_match, location, asn_match, operator_override) = {
+            let ip_db = self.ip_db.load();
+            let ip_match = ip_db.lookup_at(ip, now);
+            let location = leak::locate(&ip_db, ip, ip_match.as_ref());
+            (
+                ip_match,
+                location,
+                ip_db.lookup_asn(ip),
+                ip_db.check_override_at(ip, now),
+            )
+        };
+        let category = ip_match.as_ref().and_then(|m| m.category);
+        let ip_check = category.is_some();
+        let ip_score = ip_match
+            .as_ref()
+            .map_or(0.0, |m| self.category_weights.score(m, now, self.half_life));
+        let agreeing_sources = ip_match.as_ref().map_or(0, |m| {
+            m.sources.iter().filter(|s| s.category.is_some()).count()
+        });
+        let asn_score = asn_match
+            .as_ref()
+            .and_then(|m| m.kind)
+            .map_or(0.0, |kind| self.asn_type_weights.weight(kind));
 
-        let dns_detector = self.dns_detector.lock().await;
-        let dns_score = dns_detector.check_vpn_patterns(ip).await?;
+        if let Some(operator_override) = operator_override {
+            let is_vpn = operator_override.verdict == Verdict::Flag;
+            return Ok(DetectionResult {
+                is_vpn,
+                score: if is_vpn { 1.0 } else { 0.0 },
+                details: DetectionDetails {
+                    ip_check,
+                    ip_match,
+                    category,
+                    ip_score,
+                    agreeing_sources,
+                    location,
+                    asn_match,
+                    asn_score,
+                    dns_score: 0.0,
+                    ptr_rule_matches: Vec::new(),
+                    fcrdns: None,
+                    reverse_zone: None,
+                    zone_rule_matches: Vec::new(),
+                    dnsbl_listings: Vec::new(),
+                    ttl_analysis: None,
+                    operator_override: Some(operator_override),
+                },
+            });
+        }
 
-        let total_score = ip_check as u8 as f32 * 0.7 + dns_score;
+        let dns_report = self.dns_detector.check_vpn_patterns(ip).await?;
+        let dns_score = dns_report.score;
+        // Range and ASN evidence often describe the same fact (a hosting
+        // range inside a hosting ASN), so they combine as independent
+        // chances rather than adding up.
+        let database_score = 1.0 - (1.0 - ip_score) * (1.0 - asn_score);
+        let total_score = database_score + dns_score;
 
         Ok(DetectionResult {
             is_vpn: total_score >= 0.8,
             score: total_score,
             details: DetectionDetails {
                 ip_check,
+                ip_match,
+                category,
+                ip_score,
+                agreeing_sources,
+                location,
+                asn_match,
+                asn_score,
                 dns_score,
+                ptr_rule_matches: dns_report.rule_matches,
+                fcrdns: Some(dns_report.fcrdns),
+                reverse_zone: dns_report.reverse_zone,
+                zone_rule_matches: dns_report.zone_rule_matches,
+                dnsbl_listings: dns_report.dnsbl_listings,
                 ttl_analysis: None,
+                operator_override: None,
             },
         })
     }
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{write, NoDns};
+use detector::{VpnDetector, VpnDetectorImpl};
+use geo_ip::{AsnType, GeoIpError, IpDatabase};
+
+#[tokio::test]
+async fn hosting_asn_is_flagged_without_a_listed_range() {
+    let feed = write(
+        "asn-feed.csv",
+        "cidr,asn,provider,category\n203.0.113.0/24,64503,Example VPN,vpn\n",
+    );
+    let table = write(
+        "asn-types.csv",
+        "asn,organization,type\n\
+         AS64500,Example Cloud,hosting\n\
+         64501,Example Telecom,isp\n\
+         64503,Example VPN Inc,VPN-provider\n",
+    );
+    let pfx2as = write(
+        "pfx2as.txt",
+        "198.51.100.0\t22\t64500\n\
+         192.0.2.0\t24\t64501_64502\n\
+         2001:db8::\t32\t64500\n",
+    );
+
+    let mut ip_db = IpDatabase::load_from_csv(&feed).unwrap();
+    assert_eq!(ip_db.load_asn_table(&table).unwrap(), 3);
+    assert_eq!(ip_db.load_prefix_to_asn(&pfx2as).unwrap(), 3);
+    let detector = VpnDetectorImpl::new(ip_db, NoDns);
+
+    let hosting = detector
+        .check_vpn("198.51.101.7".parse().unwrap())
+        .await
+        .unwrap();
+    assert!(!hosting.details.ip_check);
+    let asn_match = hosting.details.asn_match.unwrap();
+    assert_eq!(asn_match.asn, 64500);
+    assert_eq!(asn_match.prefix.unwrap().to_string(), "198.51.100.0/22");
+    assert_eq!(asn_match.kind, Some(AsnType::Hosting));
+    assert_eq!(asn_match.organization.as_deref(), Some("Example Cloud"));
+    assert!((hosting.details.asn_score - 0.3).abs() < 1e-6);
+    assert!((hosting.score - 0.3).abs() < 1e-6);
+
+    let isp = detector
+        .check_vpn("192.0.2.1".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(isp.details.asn_match.unwrap().kind, Some(AsnType::Isp));
+    assert_eq!(isp.details.asn_score, 0.0);
+
+    // No announced prefix: the ASN comes from the matching feed range, and
+    // range and ASN evidence combine without exceeding 1.
+    let listed = detector
+        .check_vpn("203.0.113.9".parse().unwrap())
+        .await
+        .unwrap();
+    let asn_match = listed.details.asn_match.unwrap();
+    assert_eq!(asn_match.prefix, None);
+    assert_eq!(asn_match.kind, Some(AsnType::VpnProvider));
+    assert!((listed.score - (1.0 - 0.3 * 0.4)).abs() < 1e-6);
+
+    let unknown = detector
+        .check_vpn("10.0.0.1".parse().unwrap())
+        .await
+        .unwrap();
+    assert!(unknown.details.asn_match.is_none());
+
+    for path in [feed, table, pfx2as] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn malformed_asn_inputs_are_rejected() {
+    let table = write(
+        "bad-asn-types.csv",
+        "asn,organization,type\n64500,Example,datacenter\n",
+    );
+    let pfx2as = write(
+        "bad-pfx2as.txt",
+        "198.51.100.0\t22\t64500\n198.51.100.0\t33\t1\n",
+    );
+
+    let mut ip_db = IpDatabase::default();
+    assert!(matches!(
+        ip_db.load_asn_table(&table),
+        Err(GeoIpError::InvalidAsnType(ref kind)) if kind == "datacenter"
+    ));
+    assert!(matches!(
+        ip_db.load_prefix_to_asn(&pfx2as),
+        Err(GeoIpError::InvalidPrefixToAsn { line: 2, .. })
+    ));
+
+    std::fs::remove_file(table).unwrap();
+    std::fs::remove_file(pfx2as).unwrap();
+}
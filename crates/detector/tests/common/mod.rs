@@ -0,0 +1,49 @@
+#![allow(dead_code)]
+
+use async_trait::async_trait;
+use detector::Clock;
+use dns_check::{DnsAnalyzer, DnsError, DnsReport};
+use geo_ip::IpDatabase;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// DNS stand-in that finds nothing, for checks decided by the IP database.
+pub struct NoDns;
+
+#[async_trait]
+impl DnsAnalyzer for NoDns {
+    async fn check_vpn_patterns(&self, _ip: IpAddr) -> Result<DnsReport, DnsError> {
+        Ok(DnsReport::default())
+    }
+}
+
+/// Clock stopped at a given time.
+pub struct FixedClock(pub SystemTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+/// Writes `contents` to a file in the system temp dir that is unique to
+/// this test process and returns its path.
+pub fn write(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("detector-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// The database in `tests/fixtures/vpn_ips.csv`.
+pub fn fixture_db() -> IpDatabase {
+    IpDatabase::load_from_csv(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/vpn_ips.csv"
+    ))
+    .expect("fixture database loads")
+}
+
+pub fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use async_trait::async_trait;
+use common::fixture_db;
+use detector::{VpnDetector, VpnDetectorImpl};
+use dns_check::{DnsAnalyzer, DnsError, DnsReport};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const CALLS: usize = 64;
+const DNS_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(Default)]
+struct InFlight {
+    current: AtomicUsize,
+    max: AtomicUsize,
+}
+
+/// DNS stand-in with a fixed latency that records how many calls overlap.
+#[derive(Default)]
+struct SlowDns {
+    in_flight: Arc<InFlight>,
+}
+
+#[async_trait]
+impl DnsAnalyzer for SlowDns {
+    async fn check_vpn_patterns(&self, _ip: IpAddr) -> Result<DnsReport, DnsError> {
+        let now = self.in_flight.current.fetch_add(1, Ordering::SeqCst) + 1;
+        self.in_flight.max.fetch_max(now, Ordering::SeqCst);
+        tokio::time::sleep(DNS_DELAY).await;
+        self.in_flight.current.fetch_sub(1, Ordering::SeqCst);
+        Ok(DnsReport::default())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn parallel_checks_do_not_serialize() {
+    let detector = Arc::new(VpnDetectorImpl::new(fixture_db(), SlowDns::default()));
+
+    let start = Instant::now();
+    let handles: Vec<_> = (0..CALLS)
+        .map(|i| {
+            let detector = Arc::clone(&detector);
+            tokio::spawn(async move {
+                let ip: IpAddr = format!("10.{}.0.1", i % 4).parse().unwrap();
+                detector.check_vpn(ip).await.unwrap()
+            })
+        })
+        .collect();
+
+    for (i, handle) in handles.into_iter().enumerate() {
+        let result = handle.await.unwrap();
+        // 10.1.0.0/16 is the more specific, non-VPN range.
+        assert_eq!(result.details.ip_check, i % 4 != 1);
+    }
+    let elapsed = start.elapsed();
+
+    let serial = DNS_DELAY * CALLS as u32;
+    assert!(
+        elapsed < serial / 4,
+        "{CALLS} checks took {elapsed:?}, serial execution would take {serial:?}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn lookups_overlap_in_flight() {
+    let dns = SlowDns::default();
+    let in_flight = Arc::clone(&dns.in_flight);
+    let detector = Arc::new(VpnDetectorImpl::new(fixture_db(), dns));
+
+    let handles: Vec<_> = (0..CALLS)
+        .map(|_| {
+            let detector = Arc::clone(&detector);
+            tokio::spawn(async move { detector.check_vpn("2001:db8::1".parse().unwrap()).await })
+        })
+        .collect();
+    for handle in handles {
+        assert!(handle.await.unwrap().unwrap().details.ip_check);
+    }
+
+    assert!(in_flight.max.load(Ordering::SeqCst) > 1);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{write, FixedClock, NoDns};
+use detector::{VpnDetector, VpnDetectorImpl};
+use geo_ip::{GeoIpError, IpDatabase};
+use std::time::Duration;
+
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+async fn ip_score(detector: &VpnDetectorImpl<NoDns>, ip: &str) -> f32 {
+    detector
+        .check_vpn(ip.parse().unwrap())
+        .await
+        .unwrap()
+        .details
+        .ip_score
+}
+
+#[tokio::test]
+async fn score_scales_with_confidence_and_halves_per_half_life() {
+    let path = write(
+        "decay.csv",
+        "cidr,asn,provider,category,confidence,first_seen,last_seen\n\
+         192.0.2.0/24,64500,Fresh,vpn,,2024-01-01T00:00:00Z,2024-06-01T00:00:00Z\n\
+         198.51.100.0/24,64500,Unsure,vpn,0.5,,2024-06-01T00:00:00Z\n\
+         203.0.113.0/24,64500,Undated,vpn,,,\n",
+    );
+    let ip_db = IpDatabase::load_from_csv(&path).unwrap();
+    let last_seen = humantime::parse_rfc3339("2024-06-01T00:00:00Z").unwrap();
+
+    let fresh = VpnDetectorImpl::new(IpDatabase::load_from_csv(&path).unwrap(), NoDns)
+        .with_half_life(Some(30 * DAY))
+        .with_clock(FixedClock(last_seen));
+    assert!((ip_score(&fresh, "192.0.2.1").await - 0.7).abs() < 1e-6);
+    assert!((ip_score(&fresh, "198.51.100.1").await - 0.35).abs() < 1e-6);
+
+    let aged = VpnDetectorImpl::new(ip_db, NoDns)
+        .with_half_life(Some(30 * DAY))
+        .with_clock(FixedClock(last_seen + 60 * DAY));
+    assert!((ip_score(&aged, "192.0.2.1").await - 0.175).abs() < 1e-6);
+    assert!((ip_score(&aged, "198.51.100.1").await - 0.0875).abs() < 1e-6);
+    // Without a last_seen time there is nothing to decay from.
+    assert!((ip_score(&aged, "203.0.113.1").await - 0.7).abs() < 1e-6);
+
+    let hit = aged.check_vpn("192.0.2.1".parse().unwrap()).await.unwrap();
+    let ip_match = hit.details.ip_match.unwrap();
+    assert_eq!(ip_match.last_seen, Some(last_seen));
+    assert!(ip_match.first_seen.is_some());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn out_of_range_confidence_is_rejected() {
+    let path = write(
+        "bad-confidence.csv",
+        "cidr,asn,provider,confidence\n192.0.2.0/24,64500,Example,1.5\n",
+    );
+
+    let err = IpDatabase::load_from_csv(&path).unwrap_err();
+    assert!(matches!(err, GeoIpError::InvalidConfidence(ref c) if c == "1.5"));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn tor_exits_expire_by_the_detector_clock() {
+    let path = write(
+        "tor-exits.csv",
+        "ExitNode 0011BD2485AD45D984EC4159C88FC066E5E3300E\n\
+         Published 2024-06-01 00:00:00\n\
+         ExitAddress 192.0.2.10 2024-06-01 00:30:00\n",
+    );
+    let published = humantime::parse_rfc3339("2024-06-01T00:00:00Z").unwrap();
+    let load = || {
+        let mut ip_db = IpDatabase::default();
+        ip_db.merge_tor_exit_list(&path, Some(DAY)).unwrap();
+        ip_db
+    };
+
+    let fresh = VpnDetectorImpl::new(load(), NoDns).with_clock(FixedClock(published + DAY / 2));
+    let hit = fresh
+        .check_vpn("192.0.2.10".parse().unwrap())
+        .await
+        .unwrap();
+    assert!(hit.details.ip_check);
+
+    // Same database, a day and a half later: no reload needed to drop it.
+    let stale = VpnDetectorImpl::new(load(), NoDns).with_clock(FixedClock(published + DAY * 3 / 2));
+    let hit = stale
+        .check_vpn("192.0.2.10".parse().unwrap())
+        .await
+        .unwrap();
+    assert!(!hit.details.ip_check);
+    assert!(hit.details.ip_match.is_none());
+
+    std::fs::remove_file(path).unwrap();
+}
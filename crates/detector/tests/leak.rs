@@ -0,0 +1,56 @@
+mod common;
+
+use common::{fixture_db, ip, NoDns};
+use detector::VpnDetectorImpl;
+use geo_ip::Category;
+
+fn detector() -> VpnDetectorImpl<NoDns> {
+    VpnDetectorImpl::new(fixture_db(), NoDns)
+}
+
+#[test]
+fn resolvers_are_classified_and_compared_with_the_client() {
+    let detector = detector();
+    let resolvers = [ip("10.0.9.9"), ip("2001:db8::53"), ip("192.0.2.53")];
+
+    let checks = detector.check_resolvers(ip("10.0.2.3"), &resolvers);
+    let summary: Vec<_> = checks
+        .iter()
+        .map(|c| {
+            (
+                c.ip,
+                c.category,
+                c.asn_match.as_ref().map(|m| m.asn),
+                c.same_network,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (resolvers[0], Some(Category::Vpn), Some(64512), true),
+            (resolvers[1], Some(Category::Vpn), Some(64513), false),
+            (resolvers[2], None, None, false),
+        ]
+    );
+    assert_eq!(
+        checks[1].ip_match.as_ref().map(|m| &*m.provider),
+        Some("ExampleVPN6")
+    );
+    assert!(checks.iter().all(|c| c.same_country.is_none()));
+}
+
+#[test]
+fn unknown_networks_fall_back_to_the_prefix() {
+    let detector = detector();
+    let checks = detector.check_resolvers(
+        ip("198.51.100.7"),
+        &[ip("198.51.100.53"), ip("198.51.101.53"), ip("2001:db9::1")],
+    );
+    let same: Vec<_> = checks.iter().map(|c| c.same_network).collect();
+    assert_eq!(same, [true, false, false]);
+
+    let checks = detector.check_resolvers(ip("2001:db9:0:1::7"), &[ip("2001:db9:0:2::53")]);
+    assert!(checks[0].same_network);
+    assert!(detector.check_resolvers(ip("10.0.0.1"), &[]).is_empty());
+}
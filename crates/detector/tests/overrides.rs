@@ -0,0 +1,100 @@
+mod common;
+
+use async_trait::async_trait;
+use common::{ip, write, FixedClock};
+use detector::{VpnDetector, VpnDetectorImpl};
+use dns_check::{DnsAnalyzer, DnsError, DnsReport};
+use geo_ip::{Category, IpDatabase, Verdict};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// DNS stand-in that reports a strong signal and counts how often it is asked.
+#[derive(Default)]
+struct CountingDns {
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl DnsAnalyzer for CountingDns {
+    async fn check_vpn_patterns(&self, _ip: IpAddr) -> Result<DnsReport, DnsError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(DnsReport {
+            score: 0.5,
+            ..DnsReport::default()
+        })
+    }
+}
+
+fn fixture_db() -> IpDatabase {
+    let feed = write(
+        "overrides-feed.csv",
+        "cidr,asn,provider,category\n10.0.0.0/8,64500,Example VPN,vpn\n",
+    );
+    let overrides = write(
+        "overrides-list.csv",
+        "cidr,verdict,reason,expires\n\
+         10.1.0.0/16,allow,office network,2026-01-01T00:00:00Z\n\
+         198.51.100.7/32,flag,abuse report,\n",
+    );
+    let mut ip_db = IpDatabase::load_from_csv(&feed).unwrap();
+    ip_db.load_overrides(&overrides).unwrap();
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(overrides).unwrap();
+    ip_db
+}
+
+#[tokio::test]
+async fn allow_override_clears_a_flagged_range_without_asking_dns() {
+    let dns = CountingDns::default();
+    let calls = Arc::clone(&dns.calls);
+    let detector = VpnDetectorImpl::new(fixture_db(), dns).with_clock(FixedClock(
+        humantime::parse_rfc3339("2025-06-01T00:00:00Z").unwrap(),
+    ));
+
+    let result = detector.check_vpn(ip("10.1.2.3")).await.unwrap();
+    assert!(!result.is_vpn);
+    assert_eq!(result.score, 0.0);
+    let operator_override = result.details.operator_override.unwrap();
+    assert_eq!(operator_override.verdict, Verdict::Allow);
+    assert_eq!(operator_override.reason.as_deref(), Some("office network"));
+    // The database verdict is still reported, for information.
+    assert!(result.details.ip_check);
+    assert_eq!(result.details.category, Some(Category::Vpn));
+    assert_eq!(result.details.dns_score, 0.0);
+    assert!(result.details.fcrdns.is_none());
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn flag_override_reports_an_unlisted_address() {
+    let dns = CountingDns::default();
+    let calls = Arc::clone(&dns.calls);
+    let detector = VpnDetectorImpl::new(fixture_db(), dns);
+
+    let result = detector.check_vpn(ip("198.51.100.7")).await.unwrap();
+    assert!(result.is_vpn);
+    assert_eq!(result.score, 1.0);
+    assert_eq!(
+        result.details.operator_override.map(|o| o.verdict),
+        Some(Verdict::Flag)
+    );
+    assert!(!result.details.ip_check);
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn expired_override_leaves_the_usual_signals_in_charge() {
+    let dns = CountingDns::default();
+    let calls = Arc::clone(&dns.calls);
+    let expires = humantime::parse_rfc3339("2026-01-01T00:00:00Z").unwrap();
+    let detector = VpnDetectorImpl::new(fixture_db(), dns)
+        .with_clock(FixedClock(expires + Duration::from_secs(1)));
+
+    let result = detector.check_vpn(ip("10.1.2.3")).await.unwrap();
+    assert!(result.details.operator_override.is_none());
+    assert!(result.is_vpn);
+    assert_eq!(result.details.dns_score, 0.5);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
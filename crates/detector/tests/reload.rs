@@ -0,0 +1,58 @@
+mod common;
+
+use common::{write, NoDns};
+use detector::{VpnDetector, VpnDetectorImpl};
+use geo_ip::IpDatabase;
+use std::path::Path;
+
+async fn reload(detector: &VpnDetectorImpl<NoDns>, path: &Path) -> bool {
+    let path = path.to_path_buf();
+    detector
+        .reload_ip_database(move || IpDatabase::load_from_csv(path))
+        .await
+        .is_ok()
+}
+
+async fn flagged(detector: &VpnDetectorImpl<NoDns>, ip: &str) -> bool {
+    detector
+        .check_vpn(ip.parse().unwrap())
+        .await
+        .unwrap()
+        .details
+        .ip_check
+}
+
+#[tokio::test]
+async fn reload_swaps_in_new_feed() {
+    let path = write(
+        "reload-valid.csv",
+        "cidr,asn,provider\n10.0.0.0/8,64512,Old\n",
+    );
+    let detector = VpnDetectorImpl::new(IpDatabase::load_from_csv(&path).unwrap(), NoDns);
+    assert!(flagged(&detector, "10.0.0.1").await);
+    assert!(!flagged(&detector, "192.0.2.1").await);
+
+    std::fs::write(&path, "cidr,asn,provider\n192.0.2.0/24,64513,New\n").unwrap();
+    assert!(reload(&detector, &path).await);
+
+    assert!(!flagged(&detector, "10.0.0.1").await);
+    assert!(flagged(&detector, "192.0.2.1").await);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn invalid_feed_keeps_previous_database() {
+    let path = write(
+        "reload-invalid.csv",
+        "cidr,asn,provider\n10.0.0.0/8,64512,Old\n",
+    );
+    let detector = VpnDetectorImpl::new(IpDatabase::load_from_csv(&path).unwrap(), NoDns);
+
+    std::fs::write(&path, "cidr,asn,provider\nnot-a-cidr,64513,Broken\n").unwrap();
+    assert!(!reload(&detector, &path).await);
+    assert!(flagged(&detector, "10.0.0.1").await);
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(!reload(&detector, &path).await);
+    assert!(flagged(&detector, "10.0.0.1").await);
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::{write, NoDns};
+use detector::{VpnDetector, VpnDetectorImpl};
+use geo_ip::{Category, IpDatabase, ListFormat, LoadMode, SourceOptions};
+
+#[tokio::test]
+async fn agreeing_sources_raise_the_ip_score() {
+    let mut ip_db = IpDatabase::default();
+    for (name, contents) in [
+        ("feed-a", "192.0.2.0/24\n198.51.100.0/24\n"),
+        ("feed-b", "192.0.2.0/25\n"),
+    ] {
+        let path = write(&format!("{}.netset", name), contents);
+        let options = SourceOptions {
+            category: Category::Vpn,
+            weight: 0.5,
+        };
+        ip_db
+            .merge_list(&path, ListFormat::Cidr, &options, LoadMode::Strict)
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+    let detector = VpnDetectorImpl::new(ip_db, NoDns);
+
+    let single = detector
+        .check_vpn("198.51.100.1".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(single.details.agreeing_sources, 1);
+    // vpn weight 0.7 scaled by source weight 0.5.
+    assert!((single.details.ip_score - 0.35).abs() < 1e-6);
+
+    let both = detector
+        .check_vpn("192.0.2.1".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(both.details.agreeing_sources, 2);
+    // 1 - (1 - 0.35)^2
+    assert!((both.details.ip_score - 0.5775).abs() < 1e-6);
+    assert!(both.score > single.score);
+}
@@ -0,0 +1,271 @@
+//! DNSBL lookups: whether the client address is listed in a blocklist zone,
+//! and what the zone's return code says about it.
+
+use crate::{DnsDetector, DnsError};
+use lru::LruCache;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv4Addr};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use trust_dns_proto::rr::{Name, RData, RecordType};
+use trust_dns_resolver::error::ResolveErrorKind;
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// Addresses whose listings, or absence of them, are remembered per zone.
+const CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(10_000).unwrap();
+/// How long a "not listed" answer is kept when the zone sends no SOA.
+const DEFAULT_NEGATIVE_TTL: Duration = Duration::from_secs(300);
+/// Upper bound on any cached answer, whatever TTL the zone gives.
+const MAX_CACHE_TTL: Duration = Duration::from_secs(3600);
+/// Category for return codes missing from the zone's table. Such listings
+/// are reported but do not score.
+const UNKNOWN_CODE_CATEGORY: &str = "listed";
+
+/// What one return code of a zone means.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnCode {
+    pub category: Arc<str>,
+    /// Score a listing with this code adds, from `0.0` to `1.0`.
+    pub weight: f32,
+}
+
+/// A DNSBL zone and its return-code table.
+#[derive(Debug, Clone)]
+pub struct DnsblZone {
+    zone: Name,
+    codes: HashMap<Ipv4Addr, ReturnCode>,
+}
+
+impl DnsblZone {
+    pub fn new(zone: &str) -> Result<Self, DnsError> {
+        let mut name =
+            Name::from_ascii(zone).map_err(|_| DnsError::InvalidDnsblZone(zone.to_string()))?;
+        if name.is_root() {
+            return Err(DnsError::InvalidDnsblZone(zone.to_string()));
+        }
+        name.set_fqdn(true);
+        Ok(Self {
+            zone: name,
+            codes: HashMap::new(),
+        })
+    }
+
+    /// Maps the A record `code`, e.g. `127.0.0.4`, to a category and weight.
+    pub fn with_code(mut self, code: Ipv4Addr, category: &str, weight: f32) -> Self {
+        let category = category.trim().to_ascii_lowercase();
+        self.codes.insert(
+            code,
+            ReturnCode {
+                category: category.into(),
+                weight,
+            },
+        );
+        self
+    }
+
+    pub fn zone(&self) -> String {
+        self.zone.to_ascii().trim_end_matches('.').to_string()
+    }
+
+    /// `<reversed octets>.<zone>` for IPv4 and `<reversed nibbles>.<zone>`
+    /// for IPv6. IPv4-mapped IPv6 addresses are looked up as IPv4.
+    pub fn query_name(&self, ip: IpAddr) -> Result<Name, DnsError> {
+        let mut name = String::new();
+        match ip.to_canonical() {
+            IpAddr::V4(ip) => {
+                for octet in ip.octets().iter().rev() {
+                    let _ = write!(name, "{}.", octet);
+                }
+            }
+            IpAddr::V6(ip) => {
+                for byte in ip.octets().iter().rev() {
+                    let _ = write!(name, "{:x}.{:x}.", byte & 0xf, byte >> 4);
+                }
+            }
+        }
+        name.push_str(&self.zone.to_ascii());
+        Name::from_ascii(&name).map_err(|_| DnsError::InvalidDnsblZone(self.zone()))
+    }
+
+    fn classify(&self, code: Ipv4Addr) -> ReturnCode {
+        self.codes
+            .get(&code)
+            .cloned()
+            .unwrap_or_else(|| ReturnCode {
+                category: UNKNOWN_CODE_CATEGORY.into(),
+                weight: 0.0,
+            })
+    }
+}
+
+/// A zone listing the address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsblListing {
+    pub zone: String,
+    /// A record returned for the address.
+    pub code: Ipv4Addr,
+    pub category: Arc<str>,
+    pub weight: f32,
+    /// The zone's TXT explanation, if it gives one.
+    pub text: Vec<String>,
+}
+
+struct CacheEntry {
+    expires: Instant,
+    listings: Vec<DnsblListing>,
+}
+
+/// Answers per zone and address, each kept until its TTL runs out.
+pub(crate) struct DnsblCache {
+    entries: Mutex<LruCache<(usize, IpAddr), CacheEntry>>,
+}
+
+impl Default for DnsblCache {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(CACHE_CAPACITY)),
+        }
+    }
+}
+
+impl DnsblCache {
+    fn get(&self, key: (usize, IpAddr)) -> Option<Vec<DnsblListing>> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        match entries.get(&key) {
+            Some(entry) if entry.expires > Instant::now() => Some(entry.listings.clone()),
+            Some(_) => {
+                entries.pop(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn put(&self, key: (usize, IpAddr), ttl: Duration, listings: Vec<DnsblListing>) {
+        let entry = CacheEntry {
+            expires: Instant::now() + ttl.min(MAX_CACHE_TTL),
+            listings,
+        };
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .put(key, entry);
+    }
+}
+
+impl DnsDetector {
+    /// Every configured zone's listings of `ip`. A zone that fails to answer
+    /// or refuses the query counts as not listing the address and is asked
+    /// again next time.
+    pub async fn dnsbl_listings(&self, ip: IpAddr) -> Vec<DnsblListing> {
+        let ip = ip.to_canonical();
+        let lookups = self
+            .dnsbl_zones
+            .iter()
+            .enumerate()
+            .map(|(index, zone)| async move {
+                if let Some(listings) = self.dnsbl_cache.get((index, ip)) {
+                    return listings;
+                }
+                match self.query_dnsbl(zone, ip).await {
+                    Ok((listings, ttl)) => {
+                        self.dnsbl_cache.put((index, ip), ttl, listings.clone());
+                        listings
+                    }
+                    Err(_) => Vec::new(),
+                }
+            });
+        futures::future::join_all(lookups)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Listings of `ip` in `zone` and how long the answer may be cached.
+    async fn query_dnsbl(
+        &self,
+        zone: &DnsblZone,
+        ip: IpAddr,
+    ) -> Result<(Vec<DnsblListing>, Duration), DnsError> {
+        let name = zone.query_name(ip)?;
+        let lookup = self.dnsbl_resolver().lookup(name.clone(), RecordType::A);
+        let lookup = match tokio::time::timeout(self.timeout, lookup).await {
+            Ok(Ok(lookup)) => lookup,
+            Ok(Err(e)) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => {
+                    let ttl = negative_ttl
+                        .map(|ttl| Duration::from_secs(ttl.into()))
+                        .unwrap_or(DEFAULT_NEGATIVE_TTL);
+                    return Ok((Vec::new(), ttl));
+                }
+                _ => return Err(DnsError::NetworkError(e)),
+            },
+            Err(_) => return Err(DnsError::Timeout),
+        };
+        let ttl = lookup
+            .valid_until()
+            .saturating_duration_since(Instant::now());
+
+        // Return codes live in 127.0.0.0/8; anything else is a wildcard or
+        // sinkhole answer, not a listing.
+        let codes: Vec<Ipv4Addr> = lookup
+            .iter()
+            .filter_map(|rdata| match rdata {
+                RData::A(a) if a.0.is_loopback() => Some(a.0),
+                _ => None,
+            })
+            .collect();
+        if codes.is_empty() {
+            return Ok((Vec::new(), ttl));
+        }
+        if codes.iter().any(|code| is_refusal(*code)) {
+            return Err(DnsError::DnsblRefused(zone.zone()));
+        }
+
+        let text = self.dnsbl_text(name).await;
+        let listings = codes
+            .into_iter()
+            .map(|code| {
+                let ReturnCode { category, weight } = zone.classify(code);
+                DnsblListing {
+                    zone: zone.zone(),
+                    code,
+                    category,
+                    weight,
+                    text: text.clone(),
+                }
+            })
+            .collect();
+        Ok((listings, ttl))
+    }
+
+    fn dnsbl_resolver(&self) -> &TokioAsyncResolver {
+        self.dnsbl_resolver.as_ref().unwrap_or(&self.resolver)
+    }
+
+    /// TXT explanation for a listing; missing or failing TXT only loses the
+    /// text.
+    async fn dnsbl_text(&self, name: Name) -> Vec<String> {
+        let lookup = self.dnsbl_resolver().txt_lookup(name);
+        match tokio::time::timeout(self.timeout, lookup).await {
+            Ok(Ok(lookup)) => lookup
+                .iter()
+                .map(|txt| {
+                    txt.iter()
+                        .map(|data| String::from_utf8_lossy(data))
+                        .collect()
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// `127.255.255.0/24` answers are Spamhaus-style errors, e.g. a query
+/// through an open or public resolver, not listings.
+fn is_refusal(code: Ipv4Addr) -> bool {
+    code.octets()[..3] == [127, 255, 255]
+}
@@ -0,0 +1,130 @@
+//! Forward-confirmed reverse DNS: a PTR name only vouches for an address if
+//! it resolves back to it, since whoever controls a reverse zone can claim
+//! any name there.
+
+use crate::{no_records, normalize, DnsDetector, DnsError};
+use std::fmt;
+use std::net::IpAddr;
+
+/// PTR names resolved forward per address; a reverse zone can list many.
+const MAX_FORWARD_LOOKUPS: usize = 8;
+
+/// Outcome of the forward-confirmed reverse DNS check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Fcrdns {
+    /// A PTR name resolves back to the address.
+    Confirmed,
+    /// The address has PTR names, but none resolves back to it.
+    Mismatch,
+    /// The address has no PTR record.
+    NoPtr,
+    /// A PTR or forward lookup failed, or none was made, so the outcome is
+    /// not evidence either way.
+    #[default]
+    Unknown,
+}
+
+impl Fcrdns {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Fcrdns::Confirmed => "confirmed",
+            Fcrdns::Mismatch => "mismatch",
+            Fcrdns::NoPtr => "no_ptr",
+            Fcrdns::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for Fcrdns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Score for each FCrDNS outcome.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FcrdnsWeights {
+    pub mismatch: f32,
+    pub no_ptr: f32,
+}
+
+impl Default for FcrdnsWeights {
+    fn default() -> Self {
+        Self {
+            mismatch: 0.2,
+            no_ptr: 0.1,
+        }
+    }
+}
+
+impl FcrdnsWeights {
+    /// A confirmed name never adds score, nor does an unsettled check.
+    pub fn weight(&self, fcrdns: Fcrdns) -> f32 {
+        match fcrdns {
+            Fcrdns::Confirmed | Fcrdns::Unknown => 0.0,
+            Fcrdns::Mismatch => self.mismatch,
+            Fcrdns::NoPtr => self.no_ptr,
+        }
+    }
+}
+
+impl DnsDetector {
+    /// PTR names of `ip`, lower-cased and without the trailing dot; empty
+    /// when the address has none.
+    pub async fn ptr_names(&self, ip: IpAddr) -> Result<Vec<String>, DnsError> {
+        match self.reverse_lookup(ip).await {
+            Ok(names) => Ok(names.iter().map(|name| normalize(name)).collect()),
+            Err(DnsError::NetworkError(e)) if no_records(&e) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Resolves `hostnames`, the PTR names of `ip`, forward and checks that
+    /// one of them has `ip` among its A or AAAA answers, whichever matches
+    /// the family of `ip`. A name whose lookup
+    /// fails can neither confirm nor refute, so if none confirms the result
+    /// is `Unknown` rather than `Mismatch`.
+    pub async fn forward_confirm(&self, ip: IpAddr, hostnames: &[String]) -> Fcrdns {
+        if hostnames.is_empty() {
+            return Fcrdns::NoPtr;
+        }
+        let mut failed = false;
+        for hostname in hostnames.iter().take(MAX_FORWARD_LOOKUPS) {
+            // Fully qualified, so no search domain is appended.
+            let name = format!("{}.", hostname);
+            // Only the client's own family: a combined lookup stops at the A
+            // records of a dual-stack name and never sees its AAAA.
+            let lookup = async {
+                match ip {
+                    IpAddr::V4(ip) => self
+                        .resolver
+                        .ipv4_lookup(name)
+                        .await
+                        .map(|addrs| addrs.iter().any(|addr| addr.0 == ip)),
+                    IpAddr::V6(ip) => self
+                        .resolver
+                        .ipv6_lookup(name)
+                        .await
+                        .map(|addrs| addrs.iter().any(|addr| addr.0 == ip)),
+                }
+            };
+            match tokio::time::timeout(self.timeout, lookup).await {
+                Ok(Ok(true)) => return Fcrdns::Confirmed,
+                Ok(Ok(false)) => {}
+                Ok(Err(e)) if no_records(&e) => {}
+                Ok(Err(_)) | Err(_) => failed = true,
+            }
+        }
+        if failed {
+            Fcrdns::Unknown
+        } else {
+            Fcrdns::Mismatch
+        }
+    }
+
+    /// Looks up the PTR names of `ip` and forward-confirms them.
+    pub async fn fcrdns(&self, ip: IpAddr) -> Result<Fcrdns, DnsError> {
+        let hostnames = self.ptr_names(ip).await?;
+        Ok(self.forward_confirm(ip, &hostnames).await)
+    }
+}
@@ -1,13 +1,31 @@
+//! DNS signals about a client address: what its PTR names say, whether they
+//! resolve back to it, who runs its reverse zone and which blocklists list
+//! it. Every query is about the client address itself.
+
 use async_trait::async_trait;
-use std::{net::IpAddr, time::Duration};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 use thiserror::Error;
 use trust_dns_proto::op::ResponseCode;
 use trust_dns_resolver::{
-    config::{ResolverConfig, ResolverOpts},
-    error::ResolveError,
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
     TokioAsyncResolver,
 };
 
+mod dnsbl;
+mod fcrdns;
+mod rules;
+mod zone;
+
+pub use dnsbl::{DnsblListing, DnsblZone, ReturnCode};
+pub use fcrdns::{Fcrdns, FcrdnsWeights};
+pub use rules::{PtrRule, PtrRules, RuleMatch};
+pub use zone::ReverseZone;
+
 #[derive(Error, Debug)]
 pub enum DnsError {
     #[error("DNS resolution timeout")]
@@ -16,17 +34,54 @@ pub enum DnsError {
     ServerError(ResponseCode),
     #[error("Network error: {0}")]
     NetworkError(#[from] ResolveError),
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Invalid PTR rule file: {0}")]
+    InvalidRuleFile(String),
+    #[error("Invalid PTR rule {pattern}: {reason}")]
+    InvalidRule { pattern: String, reason: String },
+    #[error("Invalid DNSBL zone: {0}")]
+    InvalidDnsblZone(String),
+    #[error("DNSBL zone {0} refused the query from this resolver")]
+    DnsblRefused(String),
+}
+
+/// What the DNS checks found out about an address.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DnsReport {
+    /// PTR names of the address, lower-cased and without the trailing dot.
+    pub hostnames: Vec<String>,
+    /// PTR rules matching those names, in rule order.
+    pub rule_matches: Vec<RuleMatch>,
+    /// Whether one of the names resolves back to the address.
+    pub fcrdns: Fcrdns,
+    /// Reverse zone of the address, if it publishes an SOA.
+    pub reverse_zone: Option<ReverseZone>,
+    /// Zone rules matching the reverse zone's owner names, in rule order.
+    pub zone_rule_matches: Vec<RuleMatch>,
+    /// DNSBL zones listing the address, in zone order.
+    pub dnsbl_listings: Vec<DnsblListing>,
+    /// PTR rules, zone rules, the FCrDNS outcome and DNSBL listings
+    /// combined as independent evidence.
+    pub score: f32,
 }
 
 #[async_trait]
 pub trait DnsAnalyzer {
-    async fn check_vpn_patterns(&self, ip: IpAddr) -> Result<f32, DnsError>;
+    async fn check_vpn_patterns(&self, ip: IpAddr) -> Result<DnsReport, DnsError>;
 }
 
 #[derive(Clone)]
 pub struct DnsDetector {
     resolver: TokioAsyncResolver,
+    opts: ResolverOpts,
     timeout: Duration,
+    ptr_rules: Arc<PtrRules>,
+    fcrdns_weights: FcrdnsWeights,
+    dnsbl_zones: Arc<Vec<DnsblZone>>,
+    dnsbl_cache: Arc<dnsbl::DnsblCache>,
+    /// Resolver for DNSBL queries when it differs from `resolver`.
+    dnsbl_resolver: Option<TokioAsyncResolver>,
 }
 
 impl DnsDetector {
@@ -38,27 +93,54 @@ impl DnsDetector {
 
         Self {
             resolver,
+            opts,
             timeout: Duration::from_secs(timeout_sec),
+            ptr_rules: Arc::new(PtrRules::default()),
+            fcrdns_weights: FcrdnsWeights::default(),
+            dnsbl_zones: Arc::new(Vec::new()),
+            dnsbl_cache: Arc::new(dnsbl::DnsblCache::default()),
+            dnsbl_resolver: None,
         }
     }
 
-    pub async fn reverse_lookup(&self, ip: IpAddr) -> Result<Vec<String>, DnsError> {
-        let result = tokio::time::timeout(self.timeout, self.resolver.reverse_lookup(ip)).await;
+    /// Sends every query to the name server at `addr` over UDP instead of
+    /// the system default.
+    pub fn with_name_server(mut self, addr: SocketAddr) -> Self {
+        self.resolver = resolver_for(addr, self.opts);
+        self
+    }
 
-        match result {
-            Ok(Ok(lookup)) => Ok(lookup.iter().map(|name| name.to_string()).collect()),
-            Ok(Err(e)) => Err(DnsError::NetworkError(e)),
-            Err(_) => Err(DnsError::Timeout),
-        }
+    /// Sends DNSBL queries to the resolver at `addr` instead. Blocklists
+    /// such as Spamhaus refuse queries arriving through large public
+    /// resolvers, so they usually need a local one.
+    pub fn with_dnsbl_name_server(mut self, addr: SocketAddr) -> Self {
+        self.dnsbl_resolver = Some(resolver_for(addr, self.opts));
+        self
     }
 
-    pub async fn measure_resolve_time(&self, domain: &str) -> Result<Duration, DnsError> {
-        let start = std::time::Instant::now();
+    /// Replaces the built-in PTR rule with `ptr_rules`.
+    pub fn with_ptr_rules(mut self, ptr_rules: PtrRules) -> Self {
+        self.ptr_rules = Arc::new(ptr_rules);
+        self
+    }
 
-        let result = tokio::time::timeout(self.timeout, self.resolver.lookup_ip(domain)).await;
+    pub fn with_fcrdns_weights(mut self, fcrdns_weights: FcrdnsWeights) -> Self {
+        self.fcrdns_weights = fcrdns_weights;
+        self
+    }
+
+    /// Checks every address against `dnsbl_zones`; none by default.
+    pub fn with_dnsbl_zones(mut self, dnsbl_zones: Vec<DnsblZone>) -> Self {
+        self.dnsbl_zones = Arc::new(dnsbl_zones);
+        self.dnsbl_cache = Arc::new(dnsbl::DnsblCache::default());
+        self
+    }
+
+    pub async fn reverse_lookup(&self, ip: IpAddr) -> Result<Vec<String>, DnsError> {
+        let result = tokio::time::timeout(self.timeout, self.resolver.reverse_lookup(ip)).await;
 
         match result {
-            Ok(Ok(_)) => Ok(start.elapsed()),
+            Ok(Ok(lookup)) => Ok(lookup.iter().map(|name| name.to_string()).collect()),
             Ok(Err(e)) => Err(DnsError::NetworkError(e)),
             Err(_) => Err(DnsError::Timeout),
         }
@@ -67,29 +149,75 @@ impl DnsDetector {
 
 #[async_trait]
 impl DnsAnalyzer for DnsDetector {
-    async fn check_vpn_patterns(&self, ip: IpAddr) -> Result<f32, DnsError> {
-        let mut score = 0.0;
-
-        let hostnames = self.reverse_lookup(ip).await?;
-        if hostnames
+    async fn check_vpn_patterns(&self, ip: IpAddr) -> Result<DnsReport, DnsError> {
+        let ptr = async {
+            match self.ptr_names(ip).await {
+                Ok(hostnames) => {
+                    let fcrdns = self.forward_confirm(ip, &hostnames).await;
+                    (hostnames, fcrdns)
+                }
+                // A failed PTR lookup is not a missing PTR; it scores nothing
+                // rather than failing the other signals.
+                Err(_) => (Vec::new(), Fcrdns::Unknown),
+            }
+        };
+        let ((hostnames, fcrdns), reverse_zone, dnsbl_listings) =
+            tokio::join!(ptr, self.reverse_zone(ip), self.dnsbl_listings(ip));
+        // Lame or broken reverse delegations are common; they only hide who
+        // owns the zone and should not fail the other signals.
+        let reverse_zone = reverse_zone.ok().flatten();
+
+        let rule_matches = self.ptr_rules.evaluate(&hostnames);
+        let zone_rule_matches = reverse_zone
+            .as_ref()
+            .map(|zone| self.ptr_rules.evaluate_zone(&zone.owner_names()))
+            .unwrap_or_default();
+        let signals = [
+            rule_matches.iter().map(|m| m.contribution).sum(),
+            zone_rule_matches.iter().map(|m| m.contribution).sum(),
+            self.fcrdns_weights.weight(fcrdns),
+            1.0 - dnsbl_listings
+                .iter()
+                .map(|l| 1.0 - l.weight.clamp(0.0, 1.0))
+                .product::<f32>(),
+        ];
+        let miss: f32 = signals
             .iter()
-            .any(|h| h.contains("vpn") || h.ends_with(".vps"))
-        {
-            score += 0.4;
-        }
+            .map(|score: &f32| 1.0 - score.clamp(0.0, 1.0))
+            .product();
+
+        Ok(DnsReport {
+            hostnames,
+            rule_matches,
+            fcrdns,
+            reverse_zone,
+            zone_rule_matches,
+            dnsbl_listings,
+            score: 1.0 - miss,
+        })
+    }
+}
 
-        let resolve_time = self.measure_resolve_time("example.com").await?;
-        if resolve_time > Duration::from_millis(500) {
-            score += 0.2;
-        }
+/// Resolver sending every query to `addr` over UDP.
+fn resolver_for(addr: SocketAddr, opts: ResolverOpts) -> TokioAsyncResolver {
+    let name_servers = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+    let config = ResolverConfig::from_parts(None, Vec::new(), name_servers);
+    TokioAsyncResolver::tokio(config, opts)
+}
 
-        let first_lookup = self.measure_resolve_time("google.com").await?;
-        let second_lookup = self.measure_resolve_time("google.com").await?;
+/// Lower-cases a DNS name and drops the trailing dot.
+pub(crate) fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
 
-        if (first_lookup - second_lookup).as_millis() > 100 {
-            score += 0.3;
+/// NXDOMAIN and empty answers both mean the name has no such record. The
+/// resolver reports SERVFAIL and REFUSED the same way; those are failures.
+pub(crate) fn no_records(e: &ResolveError) -> bool {
+    matches!(
+        e.kind(),
+        ResolveErrorKind::NoRecordsFound {
+            response_code: ResponseCode::NXDomain | ResponseCode::NoError,
+            ..
         }
-
-        Ok(score)
-    }
+    )
 }
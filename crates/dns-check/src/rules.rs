@@ -0,0 +1,220 @@
+//! PTR hostname rules: glob or regex patterns that score an address by what
+//! its reverse DNS names, and the names of whoever runs its reverse zone,
+//! say about it.
+
+use crate::DnsError;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+
+/// One pattern from the rule file.
+#[derive(Debug, Clone)]
+pub struct PtrRule {
+    /// The pattern as written, for reporting.
+    pub pattern: Arc<str>,
+    /// What a match says about the address, e.g. `vpn` or `hosting`.
+    pub category: Arc<str>,
+    /// Score a match adds, from `0.0` to `1.0`.
+    pub weight: f32,
+    regex: Regex,
+}
+
+impl PtrRule {
+    /// Globs match the whole hostname; `*` matches any run of characters,
+    /// dots included, and `?` any single character.
+    pub fn glob(pattern: &str, category: &str, weight: f32) -> Result<Self, DnsError> {
+        let mut regex = String::from("^");
+        for c in pattern.chars() {
+            match c {
+                '*' => regex.push_str(".*"),
+                '?' => regex.push('.'),
+                c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        regex.push('$');
+        Self::build(pattern, &regex, category, weight)
+    }
+
+    /// Regexes match anywhere in the hostname unless anchored.
+    pub fn regex(pattern: &str, category: &str, weight: f32) -> Result<Self, DnsError> {
+        Self::build(pattern, pattern, category, weight)
+    }
+
+    fn build(pattern: &str, regex: &str, category: &str, weight: f32) -> Result<Self, DnsError> {
+        let invalid = |reason: String| DnsError::InvalidRule {
+            pattern: pattern.to_string(),
+            reason,
+        };
+        if !(0.0..=1.0).contains(&weight) {
+            return Err(invalid(format!("weight {} is outside 0.0..=1.0", weight)));
+        }
+        let category = category.trim().to_ascii_lowercase();
+        if category.is_empty() {
+            return Err(invalid("category is empty".to_string()));
+        }
+        let regex = RegexBuilder::new(regex)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(Self {
+            pattern: pattern.into(),
+            category: category.into(),
+            weight,
+            regex,
+        })
+    }
+
+    pub fn is_match(&self, hostname: &str) -> bool {
+        self.regex.is_match(hostname)
+    }
+}
+
+/// A rule that matched one of the address's PTR names, or one of the
+/// owner names of its reverse zone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleMatch {
+    /// First name the rule matched.
+    pub hostname: String,
+    pub pattern: Arc<str>,
+    pub category: Arc<str>,
+    pub weight: f32,
+    /// Score the rule actually added; less than `weight` once the total
+    /// reaches `1.0`.
+    pub contribution: f32,
+}
+
+/// Ordered PTR hostname rules, plus rules for the reverse zone's name
+/// servers and SOA names.
+///
+/// The rule file is TOML with one `[[rules]]` table per PTR pattern and
+/// one `[[zone_rules]]` table per reverse-zone owner pattern:
+///
+/// ```toml
+/// [[rules]]
+/// glob = "*.m247.com"
+/// category = "hosting"
+/// weight = 0.3
+///
+/// [[rules]]
+/// regex = '(^|[.-])vpn\d*[.-]'
+/// category = "vpn"
+/// weight = 0.4
+///
+/// [[zone_rules]]
+/// glob = "*.m247.com"
+/// category = "hosting"
+/// weight = 0.2
+/// ```
+#[derive(Debug, Clone)]
+pub struct PtrRules {
+    rules: Vec<PtrRule>,
+    zone_rules: Vec<PtrRule>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default)]
+    rules: Vec<RuleEntry>,
+    #[serde(default)]
+    zone_rules: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    glob: Option<String>,
+    regex: Option<String>,
+    category: String,
+    weight: f32,
+}
+
+impl Default for PtrRules {
+    /// A single rule flagging names that mention `vpn` or end in `.vps`.
+    fn default() -> Self {
+        let rule = PtrRule::regex(r"vpn|\.vps$", "vpn", 0.4).expect("built-in rule is valid");
+        Self::new(vec![rule])
+    }
+}
+
+impl PtrRules {
+    pub fn new(rules: Vec<PtrRule>) -> Self {
+        Self {
+            rules,
+            zone_rules: Vec::new(),
+        }
+    }
+
+    pub fn with_zone_rules(mut self, zone_rules: Vec<PtrRule>) -> Self {
+        self.zone_rules = zone_rules;
+        self
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, DnsError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, DnsError> {
+        let file: RuleFile =
+            toml::from_str(text).map_err(|e| DnsError::InvalidRuleFile(e.to_string()))?;
+        Ok(Self {
+            rules: build(file.rules)?,
+            zone_rules: build(file.zone_rules)?,
+        })
+    }
+
+    pub fn rules(&self) -> &[PtrRule] {
+        &self.rules
+    }
+
+    pub fn zone_rules(&self) -> &[PtrRule] {
+        &self.zone_rules
+    }
+
+    /// Every PTR rule matching at least one of `hostnames`, in rule order.
+    ///
+    /// Each rule counts once however many names it matches, and the
+    /// contributions add up to at most `1.0`.
+    pub fn evaluate(&self, hostnames: &[String]) -> Vec<RuleMatch> {
+        evaluate(&self.rules, hostnames)
+    }
+
+    /// Like [`PtrRules::evaluate`], for the zone rules and the reverse
+    /// zone's owner names.
+    pub fn evaluate_zone(&self, owners: &[String]) -> Vec<RuleMatch> {
+        evaluate(&self.zone_rules, owners)
+    }
+}
+
+fn build(entries: Vec<RuleEntry>) -> Result<Vec<PtrRule>, DnsError> {
+    entries
+        .into_iter()
+        .map(|entry| match (entry.glob, entry.regex) {
+            (Some(glob), None) => PtrRule::glob(&glob, &entry.category, entry.weight),
+            (None, Some(regex)) => PtrRule::regex(&regex, &entry.category, entry.weight),
+            _ => Err(DnsError::InvalidRuleFile(
+                "every rule needs exactly one of glob or regex".to_string(),
+            )),
+        })
+        .collect()
+}
+
+fn evaluate(rules: &[PtrRule], hostnames: &[String]) -> Vec<RuleMatch> {
+    let mut total = 0.0;
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let hostname = hostnames.iter().find(|h| rule.is_match(h))?;
+            let contribution = rule.weight.min(1.0 - total);
+            total += contribution;
+            Some(RuleMatch {
+                hostname: hostname.clone(),
+                pattern: Arc::clone(&rule.pattern),
+                category: Arc::clone(&rule.category),
+                weight: rule.weight,
+                contribution,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,86 @@
+//! Reverse-zone ownership: the SOA and NS records of the zone an address's
+//! PTR record lives in name whoever the address block is delegated to, even
+//! when the address has no PTR record at all.
+
+use crate::{no_records, normalize, DnsDetector, DnsError};
+use std::net::IpAddr;
+use trust_dns_proto::rr::{Name, RData, RecordType};
+use trust_dns_resolver::error::ResolveErrorKind;
+
+/// The reverse zone holding an address's PTR record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReverseZone {
+    /// Zone apex, e.g. `2.0.192.in-addr.arpa`.
+    pub name: String,
+    /// SOA MNAME, the zone's primary name server.
+    pub primary_ns: String,
+    /// SOA RNAME, the responsible mailbox as a domain name.
+    pub contact: String,
+    pub name_servers: Vec<String>,
+}
+
+impl ReverseZone {
+    /// Names that say who runs the zone: its name servers, primary name
+    /// server and contact, without duplicates.
+    pub fn owner_names(&self) -> Vec<String> {
+        let mut names = self.name_servers.clone();
+        for name in [&self.primary_ns, &self.contact] {
+            if !name.is_empty() && !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+}
+
+impl DnsDetector {
+    /// Finds the reverse zone of `ip` from the SOA answering for its
+    /// `in-addr.arpa` or `ip6.arpa` name, then lists the zone's name
+    /// servers. `None` when no SOA is published.
+    pub async fn reverse_zone(&self, ip: IpAddr) -> Result<Option<ReverseZone>, DnsError> {
+        let name = Name::from(ip);
+        let lookup = self.resolver.lookup(name, RecordType::SOA);
+        let soa = match tokio::time::timeout(self.timeout, lookup).await {
+            // Answered only when the PTR name is itself a zone apex, as with
+            // RFC 2317 delegations.
+            Ok(Ok(lookup)) => lookup.records().iter().find_map(|record| {
+                let RData::SOA(soa) = record.data()? else {
+                    return None;
+                };
+                Some((record.name().clone(), soa.clone()))
+            }),
+            // Otherwise the zone's SOA comes back with the negative answer.
+            Ok(Err(e)) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { soa, .. } => soa
+                    .as_ref()
+                    .and_then(|soa| Some((soa.name().clone(), soa.data()?.clone()))),
+                _ => return Err(DnsError::NetworkError(e)),
+            },
+            Err(_) => return Err(DnsError::Timeout),
+        };
+        let Some((zone, soa)) = soa else {
+            return Ok(None);
+        };
+
+        let lookup = self.resolver.lookup(zone.clone(), RecordType::NS);
+        let name_servers = match tokio::time::timeout(self.timeout, lookup).await {
+            Ok(Ok(lookup)) => lookup
+                .iter()
+                .filter_map(|rdata| match rdata {
+                    RData::NS(ns) => Some(normalize(&ns.0.to_ascii())),
+                    _ => None,
+                })
+                .collect(),
+            Ok(Err(e)) if no_records(&e) => Vec::new(),
+            Ok(Err(e)) => return Err(DnsError::NetworkError(e)),
+            Err(_) => return Err(DnsError::Timeout),
+        };
+
+        Ok(Some(ReverseZone {
+            name: normalize(&zone.to_ascii()),
+            primary_ns: normalize(&soa.mname().to_ascii()),
+            contact: normalize(&soa.rname().to_ascii()),
+            name_servers,
+        }))
+    }
+}
@@ -0,0 +1,130 @@
+#![allow(dead_code)]
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
+use trust_dns_proto::op::{Message, MessageType, ResponseCode};
+use trust_dns_proto::rr::rdata::{A, AAAA, NS, PTR, SOA};
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+
+/// Authoritative stand-in that answers UDP queries from a fixed record set:
+/// matching records, an empty answer for names it knows with other types,
+/// and NXDOMAIN for everything else. Negative answers carry the SOA of the
+/// closest enclosing zone, as a real server's would. Names marked failing
+/// get SERVFAIL.
+#[derive(Clone, Default)]
+pub struct StubDns {
+    records: Arc<Mutex<Vec<Record>>>,
+    failing: Arc<Mutex<Vec<Name>>>,
+    queries: Arc<Mutex<Vec<(String, RecordType)>>>,
+}
+
+impl StubDns {
+    pub async fn start(&self) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let stub = self.clone();
+        tokio::spawn(async move {
+            let mut buf = [0; 4096];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let Ok(request) = Message::from_vec(&buf[..len]) else {
+                    continue;
+                };
+                let response = stub.answer(&request).to_vec().unwrap();
+                socket.send_to(&response, peer).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    pub fn add(&self, name: &str, ttl: u32, rdata: RData) {
+        let name = Name::from_ascii(name).unwrap();
+        self.records
+            .lock()
+            .unwrap()
+            .push(Record::from_rdata(name, ttl, rdata));
+    }
+
+    pub fn add_ptr(&self, ip: IpAddr, hostname: &str) {
+        let name = Name::from(ip).to_ascii();
+        self.add(
+            &name,
+            300,
+            RData::PTR(PTR(Name::from_ascii(hostname).unwrap())),
+        );
+    }
+
+    pub fn add_address(&self, hostname: &str, ip: IpAddr) {
+        let rdata = match ip {
+            IpAddr::V4(ip) => RData::A(A(ip)),
+            IpAddr::V6(ip) => RData::AAAA(AAAA(ip)),
+        };
+        self.add(hostname, 300, rdata);
+    }
+
+    /// Adds a zone apex with its SOA and NS records.
+    pub fn add_zone(&self, zone: &str, primary_ns: &str, contact: &str, name_servers: &[&str]) {
+        let name = |s: &str| Name::from_ascii(s).unwrap();
+        let soa = SOA::new(name(primary_ns), name(contact), 1, 3600, 600, 86400, 300);
+        self.add(zone, 300, RData::SOA(soa));
+        for ns in name_servers {
+            self.add(zone, 300, RData::NS(NS(name(ns))));
+        }
+    }
+
+    /// Answers every query for `name` with SERVFAIL.
+    pub fn add_servfail(&self, name: &str) {
+        let name = Name::from_ascii(name).unwrap();
+        self.failing.lock().unwrap().push(name);
+    }
+
+    /// Names and types queried so far, lower-cased.
+    pub fn queries(&self) -> Vec<(String, RecordType)> {
+        self.queries.lock().unwrap().clone()
+    }
+
+    fn answer(&self, request: &Message) -> Message {
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_recursion_desired(request.recursion_desired())
+            .set_recursion_available(true)
+            .set_authoritative(true);
+        let records = self.records.lock().unwrap();
+        for query in request.queries() {
+            response.add_query(query.clone());
+            self.queries
+                .lock()
+                .unwrap()
+                .push((query.name().to_lowercase().to_ascii(), query.query_type()));
+            let name = query.name();
+            if self.failing.lock().unwrap().contains(name) {
+                response.set_response_code(ResponseCode::ServFail);
+                continue;
+            }
+            let answers: Vec<_> = records
+                .iter()
+                .filter(|r| r.name() == name && r.record_type() == query.query_type())
+                .cloned()
+                .collect();
+            if !answers.is_empty() {
+                response.add_answers(answers);
+                continue;
+            }
+            if !records.iter().any(|r| r.name() == name) {
+                response.set_response_code(ResponseCode::NXDomain);
+            }
+            let zone = records
+                .iter()
+                .filter(|r| r.record_type() == RecordType::SOA && r.name().zone_of(name))
+                .max_by_key(|r| r.name().num_labels());
+            if let Some(soa) = zone {
+                response.add_name_server(soa.clone());
+            }
+        }
+        response
+    }
+}
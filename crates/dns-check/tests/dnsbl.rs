@@ -0,0 +1,202 @@
+mod common;
+
+use common::StubDns;
+use dns_check::{DnsAnalyzer, DnsDetector, DnsblZone, FcrdnsWeights};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+use trust_dns_proto::rr::rdata::{A, TXT};
+use trust_dns_proto::rr::{RData, RecordType};
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn code(last: u8) -> Ipv4Addr {
+    Ipv4Addr::new(127, 0, 0, last)
+}
+
+fn zones() -> Vec<DnsblZone> {
+    vec![
+        DnsblZone::new("bl.example")
+            .unwrap()
+            .with_code(code(2), "spam", 0.1)
+            .with_code(code(4), "Proxy", 0.5),
+        DnsblZone::new("tor.example.")
+            .unwrap()
+            .with_code(code(1), "tor-exit", 0.8),
+    ]
+}
+
+fn list(stub: &StubDns, name: &str, ttl: u32, codes: &[Ipv4Addr]) {
+    for &code in codes {
+        stub.add(name, ttl, RData::A(A(code)));
+    }
+}
+
+async fn detector(stub: &StubDns) -> DnsDetector {
+    DnsDetector::new(2)
+        .with_name_server(stub.start().await)
+        .with_dnsbl_zones(zones())
+}
+
+#[test]
+fn query_names_reverse_octets_and_nibbles() {
+    let zone = DnsblZone::new("bl.example").unwrap();
+    assert_eq!(zone.zone(), "bl.example");
+    assert_eq!(
+        zone.query_name(ip("192.0.2.10")).unwrap().to_ascii(),
+        "10.2.0.192.bl.example."
+    );
+    assert_eq!(
+        zone.query_name(ip("::ffff:192.0.2.10")).unwrap().to_ascii(),
+        "10.2.0.192.bl.example."
+    );
+    assert_eq!(
+        zone.query_name(ip("2001:db8::567:89ab"))
+            .unwrap()
+            .to_ascii(),
+        "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.bl.example."
+    );
+    assert!(DnsblZone::new(".").is_err());
+}
+
+#[tokio::test]
+async fn listings_map_return_codes_to_categories() {
+    let stub = StubDns::default();
+    list(&stub, "10.2.0.192.bl.example.", 300, &[code(4), code(9)]);
+    stub.add(
+        "10.2.0.192.bl.example.",
+        300,
+        RData::TXT(TXT::new(vec![
+            "Open proxy, see ".into(),
+            "https://bl.example/".into(),
+        ])),
+    );
+    list(&stub, "10.2.0.192.tor.example.", 300, &[code(1)]);
+    // Wildcard answers outside 127.0.0.0/8 are not listings.
+    list(
+        &stub,
+        "11.2.0.192.bl.example.",
+        300,
+        &[Ipv4Addr::new(192, 0, 2, 1)],
+    );
+    let detector = detector(&stub).await;
+
+    let listings = detector.dnsbl_listings(ip("192.0.2.10")).await;
+    let summary: Vec<_> = listings
+        .iter()
+        .map(|l| (l.zone.as_str(), l.code, &*l.category, l.weight))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("bl.example", code(4), "proxy", 0.5),
+            ("bl.example", code(9), "listed", 0.0),
+            ("tor.example", code(1), "tor-exit", 0.8),
+        ]
+    );
+    assert_eq!(listings[0].text, ["Open proxy, see https://bl.example/"]);
+    assert!(listings[2].text.is_empty());
+
+    assert!(detector.dnsbl_listings(ip("192.0.2.11")).await.is_empty());
+    assert!(detector.dnsbl_listings(ip("192.0.2.12")).await.is_empty());
+}
+
+#[tokio::test]
+async fn ipv6_addresses_are_looked_up_by_nibble() {
+    let stub = StubDns::default();
+    let name = "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.bl.example.";
+    list(&stub, name, 300, &[code(2)]);
+    let detector = detector(&stub).await;
+
+    let listings = detector.dnsbl_listings(ip("2001:db8::1")).await;
+    assert_eq!(listings.len(), 1);
+    assert_eq!(&*listings[0].category, "spam");
+}
+
+#[tokio::test]
+async fn answers_are_cached_for_their_ttl() {
+    let stub = StubDns::default();
+    list(&stub, "20.2.0.192.bl.example.", 300, &[code(2)]);
+    list(&stub, "21.2.0.192.bl.example.", 1, &[code(2)]);
+    let detector = detector(&stub).await;
+    let asked = |name: &str| {
+        stub.queries()
+            .iter()
+            .filter(|(queried, kind)| queried == name && *kind == RecordType::A)
+            .count()
+    };
+
+    for _ in 0..3 {
+        assert_eq!(detector.dnsbl_listings(ip("192.0.2.20")).await.len(), 1);
+        assert_eq!(detector.dnsbl_listings(ip("192.0.2.21")).await.len(), 1);
+    }
+    assert_eq!(asked("20.2.0.192.bl.example."), 1);
+    assert_eq!(asked("21.2.0.192.bl.example."), 1);
+
+    // "Not listed" is cached too.
+    assert!(detector.dnsbl_listings(ip("192.0.2.22")).await.is_empty());
+    assert!(detector.dnsbl_listings(ip("192.0.2.22")).await.is_empty());
+    assert_eq!(asked("22.2.0.192.bl.example."), 1);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(detector.dnsbl_listings(ip("192.0.2.20")).await.len(), 1);
+    assert_eq!(detector.dnsbl_listings(ip("192.0.2.21")).await.len(), 1);
+    assert_eq!(asked("20.2.0.192.bl.example."), 1);
+    assert_eq!(asked("21.2.0.192.bl.example."), 2);
+}
+
+#[tokio::test]
+async fn listings_add_to_the_dns_score() {
+    let stub = StubDns::default();
+    list(&stub, "30.2.0.192.bl.example.", 300, &[code(4)]);
+    let detector = detector(&stub).await.with_fcrdns_weights(FcrdnsWeights {
+        mismatch: 0.2,
+        no_ptr: 0.0,
+    });
+
+    let report = detector.check_vpn_patterns(ip("192.0.2.30")).await.unwrap();
+    assert_eq!(report.dnsbl_listings.len(), 1);
+    assert!((report.score - 0.5).abs() < 1e-6, "{}", report.score);
+
+    let report = detector.check_vpn_patterns(ip("192.0.2.31")).await.unwrap();
+    assert!(report.dnsbl_listings.is_empty());
+    assert_eq!(report.score, 0.0);
+}
+
+#[tokio::test]
+async fn refusal_codes_are_not_listings() {
+    let stub = StubDns::default();
+    // Spamhaus answers queries through public resolvers with 127.255.255.254.
+    list(
+        &stub,
+        "40.2.0.192.bl.example.",
+        300,
+        &[Ipv4Addr::new(127, 255, 255, 254), code(4)],
+    );
+    let detector = detector(&stub).await;
+
+    assert!(detector.dnsbl_listings(ip("192.0.2.40")).await.is_empty());
+}
+
+#[tokio::test]
+async fn dnsbl_queries_can_use_their_own_resolver() {
+    let stub = StubDns::default();
+    let dnsbl_stub = StubDns::default();
+    list(&dnsbl_stub, "50.2.0.192.bl.example.", 300, &[code(4)]);
+    let detector = DnsDetector::new(2)
+        .with_name_server(stub.start().await)
+        .with_dnsbl_name_server(dnsbl_stub.start().await)
+        .with_dnsbl_zones(zones());
+
+    let report = detector.check_vpn_patterns(ip("192.0.2.50")).await.unwrap();
+    assert_eq!(report.dnsbl_listings.len(), 1);
+    assert!(stub
+        .queries()
+        .iter()
+        .all(|(name, _)| name.ends_with(".in-addr.arpa.")));
+    assert!(dnsbl_stub
+        .queries()
+        .iter()
+        .all(|(name, _)| name.ends_with(".example.")));
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use common::StubDns;
+use dns_check::{DnsDetector, DnsReport, Fcrdns, FcrdnsWeights};
+use std::net::IpAddr;
+
+async fn detector(stub: &StubDns) -> DnsDetector {
+    DnsDetector::new(2).with_name_server(stub.start().await)
+}
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[tokio::test]
+async fn ptr_name_resolving_back_is_confirmed() {
+    let stub = StubDns::default();
+    stub.add_ptr(ip("192.0.2.10"), "Host10.Example.NET.");
+    stub.add_address("host10.example.net.", ip("192.0.2.10"));
+    stub.add_ptr(ip("2001:db8::10"), "v6.example.net.");
+    stub.add_address("v6.example.net.", ip("2001:db8::10"));
+    let detector = detector(&stub).await;
+
+    assert_eq!(
+        detector.ptr_names(ip("192.0.2.10")).await.unwrap(),
+        ["host10.example.net"]
+    );
+    assert_eq!(
+        detector.fcrdns(ip("192.0.2.10")).await.unwrap(),
+        Fcrdns::Confirmed
+    );
+    assert_eq!(
+        detector.fcrdns(ip("2001:db8::10")).await.unwrap(),
+        Fcrdns::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn dual_stack_names_are_confirmed_for_either_family() {
+    let stub = StubDns::default();
+    stub.add_ptr(ip("192.0.2.11"), "dual.example.net.");
+    stub.add_ptr(ip("2001:db8::11"), "dual.example.net.");
+    stub.add_address("dual.example.net.", ip("192.0.2.11"));
+    stub.add_address("dual.example.net.", ip("2001:db8::11"));
+    let detector = detector(&stub).await;
+
+    assert_eq!(
+        detector.fcrdns(ip("2001:db8::11")).await.unwrap(),
+        Fcrdns::Confirmed
+    );
+    assert_eq!(
+        detector.fcrdns(ip("192.0.2.11")).await.unwrap(),
+        Fcrdns::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn any_confirming_name_is_enough() {
+    let stub = StubDns::default();
+    stub.add_ptr(ip("192.0.2.20"), "claimed.bank.example.");
+    stub.add_ptr(ip("192.0.2.20"), "real.hoster.example.");
+    stub.add_address("claimed.bank.example.", ip("198.51.100.1"));
+    stub.add_address("real.hoster.example.", ip("192.0.2.20"));
+    let detector = detector(&stub).await;
+
+    assert_eq!(
+        detector.fcrdns(ip("192.0.2.20")).await.unwrap(),
+        Fcrdns::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn names_pointing_elsewhere_or_nowhere_are_a_mismatch() {
+    let stub = StubDns::default();
+    stub.add_ptr(ip("192.0.2.30"), "www.bank.example.");
+    stub.add_address("www.bank.example.", ip("198.51.100.1"));
+    stub.add_ptr(ip("192.0.2.31"), "dangling.example.");
+    let detector = detector(&stub).await;
+
+    assert_eq!(
+        detector.fcrdns(ip("192.0.2.30")).await.unwrap(),
+        Fcrdns::Mismatch
+    );
+    assert_eq!(
+        detector.fcrdns(ip("192.0.2.31")).await.unwrap(),
+        Fcrdns::Mismatch
+    );
+}
+
+#[tokio::test]
+async fn missing_ptr_is_reported_not_failed() {
+    let stub = StubDns::default();
+    let detector = detector(&stub).await;
+
+    assert!(detector
+        .ptr_names(ip("192.0.2.40"))
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        detector.fcrdns(ip("192.0.2.40")).await.unwrap(),
+        Fcrdns::NoPtr
+    );
+    assert_eq!(Fcrdns::NoPtr.to_string(), "no_ptr");
+}
+
+#[test]
+fn unconsulted_outcome_is_unknown_and_scores_nothing() {
+    assert_eq!(Fcrdns::default(), Fcrdns::Unknown);
+    assert_eq!(DnsReport::default().fcrdns, Fcrdns::Unknown);
+    assert_eq!(FcrdnsWeights::default().weight(Fcrdns::default()), 0.0);
+}
+
+#[tokio::test]
+async fn failed_forward_lookups_leave_the_outcome_unknown() {
+    let stub = StubDns::default();
+    stub.add_ptr(ip("192.0.2.50"), "broken.example.");
+    stub.add_servfail("broken.example.");
+    stub.add_ptr(ip("192.0.2.51"), "broken.example.");
+    stub.add_ptr(ip("192.0.2.51"), "real.hoster.example.");
+    stub.add_address("real.hoster.example.", ip("192.0.2.51"));
+    let detector = detector(&stub).await;
+
+    assert_eq!(
+        detector.fcrdns(ip("192.0.2.50")).await.unwrap(),
+        Fcrdns::Unknown
+    );
+    // Another name can still confirm.
+    assert_eq!(
+        detector.fcrdns(ip("192.0.2.51")).await.unwrap(),
+        Fcrdns::Confirmed
+    );
+}
@@ -0,0 +1,138 @@
+use dns_check::{DnsError, PtrRule, PtrRules};
+
+fn names(hostnames: &[&str]) -> Vec<String> {
+    hostnames.iter().map(|h| h.to_string()).collect()
+}
+
+#[test]
+fn globs_match_the_whole_name_and_regexes_anywhere() {
+    let glob = PtrRule::glob("*.m247.com", "hosting", 0.3).unwrap();
+    assert!(glob.is_match("vpn-42.lon.m247.com"));
+    assert!(glob.is_match("HOST.M247.COM"));
+    assert!(!glob.is_match("m247.com.example.net"));
+    assert!(!glob.is_match("xm247.com"));
+
+    let single = PtrRule::glob("srv?.example.net", "server", 0.1).unwrap();
+    assert!(single.is_match("srv1.example.net"));
+    assert!(!single.is_match("srv12.example.net"));
+
+    let regex = PtrRule::regex(r"(^|[.-])vpn\d*[.-]", "vpn", 0.4).unwrap();
+    assert!(regex.is_match("nl-vpn3.provider.example"));
+    assert!(!regex.is_match("openvpnx.example"));
+}
+
+#[test]
+fn every_matching_rule_is_reported_with_its_contribution() {
+    let rules = PtrRules::from_toml(
+        r#"
+        [[rules]]
+        regex = 'vpn'
+        category = "VPN"
+        weight = 0.5
+
+        [[rules]]
+        glob = "*.m247.com"
+        category = "hosting"
+        weight = 0.3
+
+        [[rules]]
+        glob = "*.residential.example"
+        category = "isp"
+        weight = 0.2
+
+        [[rules]]
+        regex = 'static'
+        category = "static"
+        weight = 0.4
+        "#,
+    )
+    .unwrap();
+    assert_eq!(rules.rules().len(), 4);
+
+    let matches = rules.evaluate(&names(&["vpn1.m247.com", "static-1.vpn.example"]));
+    let summary: Vec<_> = matches
+        .iter()
+        .map(|m| {
+            (
+                &*m.pattern,
+                &*m.category,
+                m.hostname.as_str(),
+                m.weight,
+                (m.contribution * 100.0).round() / 100.0,
+            )
+        })
+        .collect();
+    // Each rule counts once, and the last one only fills what is left of 1.0.
+    assert_eq!(
+        summary,
+        [
+            ("vpn", "vpn", "vpn1.m247.com", 0.5, 0.5),
+            ("*.m247.com", "hosting", "vpn1.m247.com", 0.3, 0.3),
+            ("static", "static", "static-1.vpn.example", 0.4, 0.2),
+        ]
+    );
+    let total: f32 = matches.iter().map(|m| m.contribution).sum();
+    assert!((total - 1.0).abs() < 1e-6);
+
+    assert!(rules.evaluate(&names(&["dsl-1.isp.example"])).is_empty());
+    assert!(rules.evaluate(&[]).is_empty());
+}
+
+#[test]
+fn the_built_in_rule_keeps_the_old_heuristic() {
+    let rules = PtrRules::default();
+    for hostname in ["nl1.myvpn.example", "host42.vps"] {
+        let matches = rules.evaluate(&names(&[hostname]));
+        assert_eq!(matches.len(), 1, "{}", hostname);
+        assert_eq!(matches[0].contribution, 0.4);
+        assert_eq!(&*matches[0].category, "vpn");
+    }
+    assert!(rules.evaluate(&names(&["mail.example.org"])).is_empty());
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    let cases = [
+        "[[rules]]\nregex = '('\ncategory = \"vpn\"\nweight = 0.4\n",
+        "[[rules]]\nglob = '*.vpn'\ncategory = \"vpn\"\nweight = 1.5\n",
+        "[[rules]]\nglob = '*.vpn'\ncategory = \" \"\nweight = 0.4\n",
+    ];
+    for case in cases {
+        let err = PtrRules::from_toml(case).unwrap_err();
+        assert!(matches!(err, DnsError::InvalidRule { .. }), "{}", err);
+    }
+
+    let cases = [
+        "[[rules]]\ncategory = \"vpn\"\nweight = 0.4\n",
+        "[[rules]]\nglob = '*.vpn'\nregex = 'vpn'\ncategory = \"vpn\"\nweight = 0.4\n",
+        "[[rules]]\nglob = '*.vpn'\ncategory = \"vpn\"\n",
+        "[[rules]]\nglob = '*.vpn'\ncategory = \"vpn\"\nweight = 0.4\nscore = 1\n",
+    ];
+    for case in cases {
+        let err = PtrRules::from_toml(case).unwrap_err();
+        assert!(matches!(err, DnsError::InvalidRuleFile(_)), "{}", err);
+    }
+}
+
+#[test]
+fn the_shipped_rule_file_loads() {
+    let rules = PtrRules::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../config/ptr_rules.toml"
+    ))
+    .unwrap();
+    let categories: Vec<_> = rules
+        .evaluate(&names(&["tor-exit1.relay.example"]))
+        .into_iter()
+        .map(|m| m.category)
+        .collect();
+    assert_eq!(categories, [std::sync::Arc::from("tor-exit")]);
+
+    let matches = rules.evaluate(&names(&["vpn-de-fra-01.dedi.m247.com"]));
+    let categories: Vec<_> = matches.iter().map(|m| &*m.category).collect();
+    assert_eq!(categories, ["vpn", "dedicated", "hosting"]);
+
+    let matches = rules.evaluate_zone(&names(&["ns1.m247.com", "hostmaster.m247.com"]));
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].hostname, "ns1.m247.com");
+}
@@ -0,0 +1,179 @@
+mod common;
+
+use common::StubDns;
+use dns_check::{
+    DnsAnalyzer, DnsDetector, DnsblZone, Fcrdns, FcrdnsWeights, PtrRules, ReverseZone,
+};
+use std::net::{IpAddr, Ipv4Addr};
+use trust_dns_proto::rr::rdata::A;
+use trust_dns_proto::rr::RData;
+
+const RULES: &str = r#"
+[[rules]]
+regex = 'vpn'
+category = "vpn"
+weight = 0.4
+
+[[zone_rules]]
+glob = "*.hoster.example"
+category = "hosting"
+weight = 0.3
+"#;
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+async fn detector(stub: &StubDns) -> DnsDetector {
+    DnsDetector::new(2)
+        .with_name_server(stub.start().await)
+        .with_ptr_rules(PtrRules::from_toml(RULES).unwrap())
+}
+
+fn hoster_zone(stub: &StubDns) {
+    stub.add_zone(
+        "2.0.192.in-addr.arpa.",
+        "ns1.hoster.example.",
+        "hostmaster.hoster.example.",
+        &["ns1.hoster.example.", "ns2.hoster.example."],
+    );
+}
+
+fn assert_close(got: f32, want: f32) {
+    assert!((got - want).abs() < 1e-5, "got {}, want {}", got, want);
+}
+
+#[tokio::test]
+async fn reverse_zone_comes_from_the_negative_answer_soa() {
+    let stub = StubDns::default();
+    hoster_zone(&stub);
+    let detector = detector(&stub).await;
+
+    let zone = detector
+        .reverse_zone(ip("192.0.2.7"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        zone,
+        ReverseZone {
+            name: "2.0.192.in-addr.arpa".to_string(),
+            primary_ns: "ns1.hoster.example".to_string(),
+            contact: "hostmaster.hoster.example".to_string(),
+            name_servers: vec![
+                "ns1.hoster.example".to_string(),
+                "ns2.hoster.example".to_string()
+            ],
+        }
+    );
+    assert_eq!(
+        zone.owner_names(),
+        [
+            "ns1.hoster.example",
+            "ns2.hoster.example",
+            "hostmaster.hoster.example"
+        ]
+    );
+
+    assert_eq!(
+        detector.reverse_zone(ip("198.51.100.7")).await.unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn every_signal_is_about_the_client_address() {
+    let stub = StubDns::default();
+    hoster_zone(&stub);
+    stub.add_ptr(ip("192.0.2.7"), "vpn7.hoster.example.");
+    stub.add_address("vpn7.hoster.example.", ip("192.0.2.99"));
+    let detector = detector(&stub).await;
+
+    let report = detector.check_vpn_patterns(ip("192.0.2.7")).await.unwrap();
+    assert_eq!(report.hostnames, ["vpn7.hoster.example"]);
+    assert_eq!(report.fcrdns, Fcrdns::Mismatch);
+    assert_eq!(report.rule_matches.len(), 1);
+    assert_eq!(&*report.rule_matches[0].category, "vpn");
+    assert_eq!(report.zone_rule_matches.len(), 1);
+    assert_eq!(report.zone_rule_matches[0].hostname, "ns1.hoster.example");
+    // PTR rule, zone rule and FCrDNS mismatch as independent evidence.
+    assert_close(report.score, 1.0 - 0.6 * 0.7 * 0.8);
+
+    for (name, _) in stub.queries() {
+        assert!(
+            name.ends_with(".in-addr.arpa.") || name == "vpn7.hoster.example.",
+            "unrelated query for {}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn confirmed_names_without_rule_matches_score_nothing() {
+    let stub = StubDns::default();
+    stub.add_ptr(ip("192.0.2.8"), "mail.isp.example.");
+    stub.add_address("mail.isp.example.", ip("192.0.2.8"));
+    let detector = detector(&stub).await;
+
+    let report = detector.check_vpn_patterns(ip("192.0.2.8")).await.unwrap();
+    assert_eq!(report.fcrdns, Fcrdns::Confirmed);
+    assert_eq!(report.reverse_zone, None);
+    assert!(report.rule_matches.is_empty());
+    assert_eq!(report.score, 0.0);
+}
+
+#[tokio::test]
+async fn missing_ptr_scores_the_no_ptr_weight() {
+    let stub = StubDns::default();
+    let detector = detector(&stub).await.with_fcrdns_weights(FcrdnsWeights {
+        mismatch: 0.2,
+        no_ptr: 0.15,
+    });
+
+    let report = detector.check_vpn_patterns(ip("192.0.2.9")).await.unwrap();
+    assert_eq!(report.fcrdns, Fcrdns::NoPtr);
+    assert!(report.hostnames.is_empty());
+    assert_close(report.score, 0.15);
+}
+
+#[tokio::test]
+async fn servfail_on_the_forward_lookup_keeps_the_other_signals() {
+    let stub = StubDns::default();
+    hoster_zone(&stub);
+    stub.add_ptr(ip("192.0.2.11"), "vpn11.hoster.example.");
+    stub.add_servfail("vpn11.hoster.example.");
+    let detector = detector(&stub).await;
+
+    let report = detector.check_vpn_patterns(ip("192.0.2.11")).await.unwrap();
+    assert_eq!(report.hostnames, ["vpn11.hoster.example"]);
+    assert_eq!(report.fcrdns, Fcrdns::Unknown);
+    assert_eq!(report.rule_matches.len(), 1);
+    assert_eq!(report.zone_rule_matches.len(), 1);
+    // PTR rule and zone rule only; an unknown FCrDNS outcome adds nothing.
+    assert_close(report.score, 1.0 - 0.6 * 0.7);
+}
+
+#[tokio::test]
+async fn servfail_on_the_ptr_lookup_keeps_the_other_signals() {
+    let stub = StubDns::default();
+    stub.add_servfail("12.2.0.192.in-addr.arpa.");
+    stub.add(
+        "12.2.0.192.bl.example.",
+        300,
+        RData::A(A(Ipv4Addr::new(127, 0, 0, 4))),
+    );
+    let detector = detector(&stub)
+        .await
+        .with_dnsbl_zones(vec![DnsblZone::new("bl.example").unwrap().with_code(
+            Ipv4Addr::new(127, 0, 0, 4),
+            "proxy",
+            0.5,
+        )]);
+
+    let report = detector.check_vpn_patterns(ip("192.0.2.12")).await.unwrap();
+    assert!(report.hostnames.is_empty());
+    assert_eq!(report.fcrdns, Fcrdns::Unknown);
+    assert_eq!(report.reverse_zone, None);
+    assert_eq!(report.dnsbl_listings.len(), 1);
+    assert_close(report.score, 0.5);
+}
@@ -0,0 +1,128 @@
+//! DNS leak test: unique per-session probe names under a zone this service
+//! is authoritative for, and the resolvers seen looking them up.
+//!
+//! A client that resolves `<token>.probe.example` does so through its own
+//! resolver, so the address querying [`LeakServer`] for the token is the
+//! client's resolver. A resolver far from the client's egress address is a
+//! strong sign the traffic is tunnelled while DNS is not.
+
+use rand::Rng;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use trust_dns_proto::rr::Name;
+
+mod server;
+
+pub use server::LeakServer;
+
+/// Random bytes per token; hex-encoded into a single DNS label.
+const TOKEN_BYTES: usize = 12;
+/// Resolvers remembered per token. Anyone can query the zone, so a token's
+/// list must not grow without bound.
+const MAX_RESOLVERS_PER_TOKEN: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum LeakError {
+    #[error("Invalid probe zone: {0}")]
+    InvalidZone(String),
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// A probe name handed out for one client session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    pub token: String,
+    /// `<token>.<zone>`, for the client to resolve.
+    pub hostname: String,
+}
+
+struct Session {
+    issued: Instant,
+    resolvers: Vec<IpAddr>,
+}
+
+/// Issues probe tokens and records the resolvers that look them up.
+pub struct LeakTracker {
+    zone: Name,
+    token_ttl: Duration,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl LeakTracker {
+    /// Tokens are answered for `token_ttl` after they are issued.
+    pub fn new(zone: &str, token_ttl: Duration) -> Result<Self, LeakError> {
+        let invalid = || LeakError::InvalidZone(zone.to_string());
+        let mut zone = Name::from_ascii(zone).map_err(|_| invalid())?;
+        if zone.is_root() {
+            return Err(invalid());
+        }
+        zone.set_fqdn(true);
+        Ok(Self {
+            zone: zone.to_lowercase(),
+            token_ttl,
+            sessions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// Starts a session with a fresh token and drops expired ones.
+    pub fn issue(&self) -> Probe {
+        let bytes: [u8; TOKEN_BYTES] = rand::thread_rng().gen();
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let hostname = format!("{}.{}", token, self.zone.to_ascii().trim_end_matches('.'));
+
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.issued.elapsed() < self.token_ttl);
+        sessions.insert(
+            token.clone(),
+            Session {
+                issued: Instant::now(),
+                resolvers: Vec::new(),
+            },
+        );
+        Probe { token, hostname }
+    }
+
+    /// Notes that `resolver` looked up `token`; `false` when the token is
+    /// unknown or expired.
+    pub fn record(&self, token: &str, resolver: IpAddr) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(session) = self.live(&mut sessions, token) else {
+            return false;
+        };
+        if !session.resolvers.contains(&resolver)
+            && session.resolvers.len() < MAX_RESOLVERS_PER_TOKEN
+        {
+            session.resolvers.push(resolver);
+        }
+        true
+    }
+
+    /// Resolvers that looked up `token`, in the order they were first seen;
+    /// `None` when the token is unknown or expired.
+    pub fn resolvers(&self, token: &str) -> Option<Vec<IpAddr>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let token = token.to_ascii_lowercase();
+        self.live(&mut sessions, &token)
+            .map(|session| session.resolvers.clone())
+    }
+
+    fn live<'a>(
+        &self,
+        sessions: &'a mut HashMap<String, Session>,
+        token: &str,
+    ) -> Option<&'a mut Session> {
+        if sessions.get(token)?.issued.elapsed() >= self.token_ttl {
+            sessions.remove(token);
+            return None;
+        }
+        sessions.get_mut(token)
+    }
+}
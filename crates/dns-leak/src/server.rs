@@ -0,0 +1,148 @@
+use crate::{LeakError, LeakTracker};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use trust_dns_proto::op::{Message, MessageType, OpCode, ResponseCode};
+use trust_dns_proto::rr::rdata::{A, AAAA, SOA};
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+
+/// Largest query read; plain DNS over UDP fits in 512 bytes, EDNS in more.
+const MAX_QUERY_BYTES: usize = 4096;
+
+/// Authoritative UDP name server for the probe zone.
+///
+/// Every query for `<token>.<zone>` is recorded against the token before it
+/// is answered, with TTL 0 so that the next session's lookup reaches this
+/// server again instead of a resolver cache.
+pub struct LeakServer {
+    socket: UdpSocket,
+    tracker: Arc<LeakTracker>,
+    ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+}
+
+impl LeakServer {
+    pub async fn bind(addr: SocketAddr, tracker: Arc<LeakTracker>) -> Result<Self, LeakError> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr).await?,
+            tracker,
+            ipv4: None,
+            ipv6: None,
+        })
+    }
+
+    /// Addresses probe names resolve to, e.g. a web server serving the page
+    /// that triggers the lookup. Without them probe names have no records,
+    /// which is enough to see the resolver.
+    pub fn with_answers(mut self, ipv4: Option<Ipv4Addr>, ipv6: Option<Ipv6Addr>) -> Self {
+        self.ipv4 = ipv4;
+        self.ipv6 = ipv6;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, LeakError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Answers queries until the socket fails.
+    pub async fn run(self) -> Result<(), LeakError> {
+        let mut buf = [0; MAX_QUERY_BYTES];
+        loop {
+            let (len, peer) = self.socket.recv_from(&mut buf).await?;
+            // Malformed packets and stray responses are dropped.
+            let Ok(request) = Message::from_vec(&buf[..len]) else {
+                continue;
+            };
+            let Some(response) = self.respond(&request, peer.ip()) else {
+                continue;
+            };
+            if let Ok(bytes) = response.to_vec() {
+                // A failed send only loses this answer; the resolver retries.
+                let _ = self.socket.send_to(&bytes, peer).await;
+            }
+        }
+    }
+
+    fn respond(&self, request: &Message, resolver: IpAddr) -> Option<Message> {
+        if request.message_type() != MessageType::Query {
+            return None;
+        }
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_recursion_desired(request.recursion_desired())
+            .set_authoritative(true);
+
+        let query = match request.queries() {
+            [query] if request.op_code() == OpCode::Query => query,
+            _ => {
+                response.set_response_code(ResponseCode::NotImp);
+                return Some(response);
+            }
+        };
+        response.add_query(query.clone());
+
+        let zone = self.tracker.zone();
+        let name = query.name().to_lowercase();
+        if !zone.zone_of(&name) {
+            response
+                .set_authoritative(false)
+                .set_response_code(ResponseCode::Refused);
+            return Some(response);
+        }
+
+        let answers = if name == *zone {
+            match query.query_type() {
+                RecordType::SOA => vec![self.soa()],
+                _ => Vec::new(),
+            }
+        } else {
+            let token = (name.num_labels() == zone.num_labels() + 1)
+                .then(|| name.iter().next())
+                .flatten()
+                .and_then(|label| std::str::from_utf8(label).ok());
+            match token {
+                Some(token) if self.tracker.record(token, resolver) => {
+                    self.answers(query.name(), query.query_type())
+                }
+                _ => {
+                    response.set_response_code(ResponseCode::NXDomain);
+                    Vec::new()
+                }
+            }
+        };
+
+        if answers.is_empty() {
+            // Negative answers carry the SOA, whose zero minimum keeps
+            // resolvers from caching them either.
+            response.add_name_server(self.soa());
+        }
+        response.add_answers(answers);
+        Some(response)
+    }
+
+    fn answers(&self, name: &Name, query_type: RecordType) -> Vec<Record> {
+        let rdata = match query_type {
+            RecordType::A => self.ipv4.map(|ip| RData::A(A(ip))),
+            RecordType::AAAA => self.ipv6.map(|ip| RData::AAAA(AAAA(ip))),
+            _ => None,
+        };
+        rdata
+            .map(|rdata| Record::from_rdata(name.clone(), 0, rdata))
+            .into_iter()
+            .collect()
+    }
+
+    fn soa(&self) -> Record {
+        let zone = self.tracker.zone();
+        let host = |label: &str| {
+            Name::from_ascii(label)
+                .and_then(|name| name.append_domain(zone))
+                .unwrap_or_else(|_| zone.clone())
+        };
+        let soa = SOA::new(host("ns"), host("hostmaster"), 1, 3600, 600, 86400, 0);
+        Record::from_rdata(zone.clone(), 0, RData::SOA(soa))
+    }
+}
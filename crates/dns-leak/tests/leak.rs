@@ -0,0 +1,177 @@
+use dns_leak::{LeakServer, LeakTracker};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use trust_dns_proto::op::{Message, MessageType, Query, ResponseCode};
+use trust_dns_proto::rr::{Name, RData, RecordType};
+
+const WEB: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 80);
+
+async fn start(tracker: &Arc<LeakTracker>) -> SocketAddr {
+    let server = LeakServer::bind("127.0.0.1:0".parse().unwrap(), Arc::clone(tracker))
+        .await
+        .unwrap()
+        .with_answers(Some(WEB), None);
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.run());
+    addr
+}
+
+/// Stub client standing in for a resolver: sends one query from `from` and
+/// returns the answer.
+async fn query(server: SocketAddr, from: &str, name: &str, query_type: RecordType) -> Message {
+    let socket = UdpSocket::bind((from, 0)).await.unwrap();
+    let mut request = Message::new();
+    request
+        .set_id(query_id(name))
+        .set_message_type(MessageType::Query)
+        .add_query(Query::query(Name::from_ascii(name).unwrap(), query_type));
+    socket
+        .send_to(&request.to_vec().unwrap(), server)
+        .await
+        .unwrap();
+    let mut buf = [0; 4096];
+    let (len, _) = tokio::time::timeout(Duration::from_secs(2), socket.recv_from(&mut buf))
+        .await
+        .expect("server answers")
+        .unwrap();
+    let response = Message::from_vec(&buf[..len]).unwrap();
+    assert_eq!(response.id(), request.id());
+    response
+}
+
+fn query_id(name: &str) -> u16 {
+    name.bytes()
+        .fold(7u16, |id, b| id.wrapping_mul(31).wrapping_add(b as u16))
+}
+
+fn localhost() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+#[tokio::test]
+async fn resolver_of_a_probe_is_recorded_and_answered() {
+    let tracker = Arc::new(LeakTracker::new("probe.example", Duration::from_secs(60)).unwrap());
+    let server = start(&tracker).await;
+
+    let probe = tracker.issue();
+    assert_eq!(probe.hostname, format!("{}.probe.example", probe.token));
+    assert_eq!(tracker.resolvers(&probe.token), Some(Vec::new()));
+
+    let response = query(
+        server,
+        "127.0.0.1",
+        &format!("{}.", probe.hostname),
+        RecordType::A,
+    )
+    .await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response.authoritative());
+    let answers: Vec<_> = response
+        .answers()
+        .iter()
+        .map(|r| (r.ttl(), r.data().cloned()))
+        .collect();
+    assert_eq!(answers, [(0, Some(RData::A(WEB.into())))]);
+
+    // Resolvers may randomise the case of the name; AAAA has no answer
+    // here but still counts, and a repeat lookup is recorded once.
+    let shouting = format!("{}.", probe.hostname.to_ascii_uppercase());
+    let response = query(server, "127.0.0.1", &shouting, RecordType::AAAA).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response.answers().is_empty());
+    assert_eq!(response.name_servers()[0].record_type(), RecordType::SOA);
+
+    assert_eq!(tracker.resolvers(&probe.token), Some(vec![localhost()]));
+    assert_eq!(
+        tracker.resolvers(&probe.token.to_ascii_uppercase()),
+        Some(vec![localhost()])
+    );
+}
+
+#[tokio::test]
+async fn each_session_sees_only_its_own_resolvers() {
+    let tracker = Arc::new(LeakTracker::new("probe.example.", Duration::from_secs(60)).unwrap());
+    let server = start(&tracker).await;
+
+    let first = tracker.issue();
+    let second = tracker.issue();
+    assert_ne!(first.token, second.token);
+
+    query(
+        server,
+        "127.0.0.1",
+        &format!("{}.", first.hostname),
+        RecordType::A,
+    )
+    .await;
+    query(
+        server,
+        "127.0.0.2",
+        &format!("{}.", first.hostname),
+        RecordType::A,
+    )
+    .await;
+
+    assert_eq!(
+        tracker.resolvers(&first.token),
+        Some(vec![localhost(), "127.0.0.2".parse().unwrap()])
+    );
+    assert_eq!(tracker.resolvers(&second.token), Some(Vec::new()));
+}
+
+#[tokio::test]
+async fn unknown_names_and_other_zones_are_not_recorded() {
+    let tracker = Arc::new(LeakTracker::new("probe.example", Duration::from_secs(60)).unwrap());
+    let server = start(&tracker).await;
+    let probe = tracker.issue();
+
+    let response = query(
+        server,
+        "127.0.0.1",
+        "0123abcd.probe.example.",
+        RecordType::A,
+    )
+    .await;
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    assert!(response.answers().is_empty());
+
+    let nested = format!("www.{}.", probe.hostname);
+    let response = query(server, "127.0.0.1", &nested, RecordType::A).await;
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+    let response = query(server, "127.0.0.1", "example.com.", RecordType::A).await;
+    assert_eq!(response.response_code(), ResponseCode::Refused);
+
+    let response = query(server, "127.0.0.1", "probe.example.", RecordType::SOA).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answers()[0].record_type(), RecordType::SOA);
+
+    assert_eq!(tracker.resolvers(&probe.token), Some(Vec::new()));
+    assert_eq!(tracker.resolvers("0123abcd"), None);
+}
+
+#[tokio::test]
+async fn tokens_expire() {
+    let tracker = Arc::new(LeakTracker::new("probe.example", Duration::from_millis(50)).unwrap());
+    let server = start(&tracker).await;
+    let probe = tracker.issue();
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    let response = query(
+        server,
+        "127.0.0.1",
+        &format!("{}.", probe.hostname),
+        RecordType::A,
+    )
+    .await;
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    assert_eq!(tracker.resolvers(&probe.token), None);
+}
+
+#[test]
+fn the_root_is_not_a_probe_zone() {
+    assert!(LeakTracker::new(".", Duration::from_secs(60)).is_err());
+    assert!(LeakTracker::new("bad..zone", Duration::from_secs(60)).is_err());
+}
@@ -0,0 +1,61 @@
+use crate::FeedError;
+use http_body_util::Empty;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{HeaderName, HOST, USER_AGENT};
+use hyper::{Request, Response, Uri};
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::ServerName;
+use tokio_rustls::TlsConnector;
+
+const AGENT: &str = concat!("vpn-detector-feed-sync/", env!("CARGO_PKG_VERSION"));
+
+/// Sends one `GET` over a fresh HTTP/1.1 connection, plain or TLS depending
+/// on the URL scheme.
+pub(crate) async fn get(
+    uri: &Uri,
+    headers: &[(HeaderName, String)],
+    tls: &TlsConnector,
+) -> Result<Response<Incoming>, FeedError> {
+    let invalid = || FeedError::InvalidUrl(uri.to_string());
+    let host = uri.host().ok_or_else(invalid)?;
+    let https = match uri.scheme_str() {
+        Some("https") => true,
+        Some("http") => false,
+        _ => return Err(FeedError::UnsupportedScheme(uri.to_string())),
+    };
+    let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+    let authority = uri.authority().ok_or_else(invalid)?.as_str();
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+
+    let mut request = Request::get(path)
+        .header(HOST, authority)
+        .header(USER_AGENT, AGENT);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    let request = request.body(Empty::<Bytes>::new()).map_err(|_| invalid())?;
+
+    // IPv6 literals keep their brackets in the URL but not on the socket.
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let tcp = TcpStream::connect((host, port)).await?;
+    if https {
+        let name = ServerName::try_from(host).map_err(|_| invalid())?;
+        let stream = tls.connect(name, tcp).await?;
+        send(stream, request).await
+    } else {
+        send(tcp, request).await
+    }
+}
+
+async fn send<IO>(io: IO, request: Request<Empty<Bytes>>) -> Result<Response<Incoming>, FeedError>
+where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(io)).await?;
+    // The connection has to be driven while the body is read; it ends
+    // on its own once the response is complete.
+    tokio::spawn(connection);
+    Ok(sender.send_request(request).await?)
+}
@@ -0,0 +1,331 @@
+//! Keeps local copies of anonymizer feeds in sync with an HTTP(S) mirror.
+//!
+//! Every feed is published with a detached ed25519 signature over the file's
+//! exact bytes. A download replaces the local copy only once its signature
+//! checks out, so the file on disk is always the last good version and a
+//! failed or tampered update leaves it untouched.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use http_body_util::BodyExt;
+use hyper::header::{HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use hyper::{StatusCode, Uri};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio_rustls::rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+mod http;
+mod watch;
+
+pub use watch::FeedWatcher;
+
+const SIGNATURE_LEN: usize = 64;
+const PUBLIC_KEY_LEN: usize = 32;
+/// Signatures are tiny; anything much larger is not one.
+const MAX_SIGNATURE_BYTES: usize = 1024;
+
+#[derive(Error, Debug)]
+pub enum FeedError {
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] hyper::Error),
+    #[error("Invalid feed URL: {0}")]
+    InvalidUrl(String),
+    #[error("Unsupported URL scheme, expected http or https: {0}")]
+    UnsupportedScheme(String),
+    #[error("Invalid CA certificate in {0}")]
+    InvalidCaCertificate(String),
+    #[error("Unexpected HTTP status {status} from {url}")]
+    UnexpectedStatus { status: u16, url: String },
+    #[error("Malformed signature from {0}")]
+    MalformedSignature(String),
+    #[error("Signature verification failed for {0}")]
+    InvalidSignature(String),
+    #[error("Invalid public key, expected 32 base64-encoded bytes")]
+    InvalidPublicKey,
+    #[error("Feed update timed out")]
+    Timeout,
+}
+
+/// One feed on the mirror and where its local copy lives.
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub url: Uri,
+    pub signature_url: Uri,
+    pub path: PathBuf,
+}
+
+impl Feed {
+    /// The signature defaults to `url` with `.sig` appended.
+    pub fn new(
+        url: &str,
+        signature_url: Option<&str>,
+        path: impl Into<PathBuf>,
+    ) -> Result<Self, FeedError> {
+        let parse = |url: &str| {
+            url.parse::<Uri>()
+                .map_err(|_| FeedError::InvalidUrl(url.to_string()))
+        };
+        Ok(Self {
+            url: parse(url)?,
+            signature_url: match signature_url {
+                Some(signature_url) => parse(signature_url)?,
+                None => parse(&format!("{}.sig", url))?,
+            },
+            path: path.into(),
+        })
+    }
+}
+
+/// What an update did to the local copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    /// The mirror's copy was unchanged; nothing was downloaded.
+    Unchanged,
+    /// A new, verified copy replaced the local file.
+    Updated { bytes: u64 },
+}
+
+/// Downloads feeds, verifies their signatures and swaps them into place.
+#[derive(Clone)]
+pub struct FeedUpdater {
+    public_key: [u8; PUBLIC_KEY_LEN],
+    roots: RootCertStore,
+    tls: TlsConnector,
+    timeout: Duration,
+}
+
+impl FeedUpdater {
+    /// Trusts the web PKI roots for HTTPS; add a private CA with
+    /// [`FeedUpdater::with_ca_file`].
+    pub fn new(public_key: [u8; PUBLIC_KEY_LEN]) -> Self {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        Self {
+            public_key,
+            tls: connector(roots.clone()),
+            roots,
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Also trusts the PEM certificates in `path`, e.g. the CA of an
+    /// internal mirror.
+    pub fn with_ca_file(mut self, path: impl AsRef<Path>) -> Result<Self, FeedError> {
+        let path = path.as_ref();
+        let invalid = || FeedError::InvalidCaCertificate(path.display().to_string());
+        let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))?;
+        if certs.is_empty() {
+            return Err(invalid());
+        }
+        for cert in certs {
+            self.roots.add(&Certificate(cert)).map_err(|_| invalid())?;
+        }
+        self.tls = connector(self.roots.clone());
+        Ok(self)
+    }
+
+    /// Limit for one feed's download and verification; one minute by default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Fetches `feed` unless the mirror reports it unchanged since the last
+    /// update, and replaces the local copy once the signature verifies.
+    ///
+    /// The `ETag` and `Last-Modified` validators of the current copy are kept
+    /// next to it in `<path>.meta`.
+    pub async fn update(&self, feed: &Feed) -> Result<Update, FeedError> {
+        let download = download_path(&feed.path);
+        let result = tokio::time::timeout(self.timeout, self.fetch(feed, &download))
+            .await
+            .unwrap_or(Err(FeedError::Timeout));
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&download).await;
+        }
+        result
+    }
+
+    async fn fetch(&self, feed: &Feed, download: &Path) -> Result<Update, FeedError> {
+        let meta = meta_path(&feed.path);
+        // Validators are only worth sending if the copy they describe exists.
+        let validators = if tokio::fs::try_exists(&feed.path).await? {
+            Validators::load(&meta).await
+        } else {
+            Validators::default()
+        };
+
+        let response = http::get(&feed.url, &validators.headers(), &self.tls).await?;
+        match response.status() {
+            StatusCode::NOT_MODIFIED => return Ok(Update::Unchanged),
+            StatusCode::OK => {}
+            status => return Err(unexpected(status, &feed.url)),
+        }
+        let fresh = Validators::from_response(response.headers());
+
+        let mut body = response.into_body();
+        let mut file = tokio::fs::File::create(download).await?;
+        let mut bytes = 0;
+        while let Some(frame) = body.frame().await {
+            if let Some(data) = frame?.data_ref() {
+                file.write_all(data).await?;
+                bytes += data.len() as u64;
+            }
+        }
+        file.sync_all().await?;
+        drop(file);
+
+        let signature = self.fetch_signature(&feed.signature_url).await?;
+        // Ed25519 signs the whole message at once, so the download is read
+        // back in full to verify it.
+        let contents = tokio::fs::read(download).await?;
+        UnparsedPublicKey::new(&ED25519, &self.public_key)
+            .verify(&contents, &signature)
+            .map_err(|_| FeedError::InvalidSignature(feed.url.to_string()))?;
+
+        tokio::fs::rename(download, &feed.path).await?;
+        fresh.save(&meta).await?;
+        Ok(Update::Updated { bytes })
+    }
+
+    async fn fetch_signature(&self, url: &Uri) -> Result<Vec<u8>, FeedError> {
+        let response = http::get(url, &[], &self.tls).await?;
+        if response.status() != StatusCode::OK {
+            return Err(unexpected(response.status(), url));
+        }
+        let body = response.into_body().collect().await?.to_bytes();
+        let malformed = || FeedError::MalformedSignature(url.to_string());
+        if body.len() > MAX_SIGNATURE_BYTES {
+            return Err(malformed());
+        }
+
+        // Raw 64-byte signatures and base64 text files are both common.
+        let signature = if body.len() == SIGNATURE_LEN {
+            body.to_vec()
+        } else {
+            let text = std::str::from_utf8(&body).map_err(|_| malformed())?;
+            BASE64.decode(text.trim()).map_err(|_| malformed())?
+        };
+        if signature.len() != SIGNATURE_LEN {
+            return Err(malformed());
+        }
+        Ok(signature)
+    }
+}
+
+/// Decodes a base64 ed25519 public key as found in the settings.
+pub fn decode_public_key(encoded: &str) -> Result<[u8; PUBLIC_KEY_LEN], FeedError> {
+    BASE64
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or(FeedError::InvalidPublicKey)
+}
+
+fn connector(roots: RootCertStore) -> TlsConnector {
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+fn unexpected(status: StatusCode, url: &Uri) -> FeedError {
+    FeedError::UnexpectedStatus {
+        status: status.as_u16(),
+        url: url.to_string(),
+    }
+}
+
+fn download_path(path: &Path) -> PathBuf {
+    sibling(path, "download")
+}
+
+fn meta_path(path: &Path) -> PathBuf {
+    sibling(path, "meta")
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// HTTP cache validators of the local copy.
+#[derive(Debug, Default)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Missing or unreadable metadata just means an unconditional download.
+    async fn load(path: &Path) -> Self {
+        let mut validators = Self::default();
+        let Ok(text) = tokio::fs::read_to_string(path).await else {
+            return validators;
+        };
+        for line in text.lines() {
+            match line.split_once(": ") {
+                Some(("etag", value)) => validators.etag = Some(value.to_string()),
+                Some(("last-modified", value)) => {
+                    validators.last_modified = Some(value.to_string())
+                }
+                _ => {}
+            }
+        }
+        validators
+    }
+
+    fn from_response(headers: &hyper::HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    fn headers(&self) -> Vec<(HeaderName, String)> {
+        let mut headers = Vec::new();
+        if let Some(etag) = &self.etag {
+            headers.push((IF_NONE_MATCH, etag.clone()));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.push((IF_MODIFIED_SINCE, last_modified.clone()));
+        }
+        headers
+    }
+
+    async fn save(&self, path: &Path) -> Result<(), FeedError> {
+        let mut text = String::new();
+        if let Some(etag) = &self.etag {
+            text.push_str(&format!("etag: {}\n", etag));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            text.push_str(&format!("last-modified: {}\n", last_modified));
+        }
+        tokio::fs::write(path, text).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+//! Change detection for local feed files that other tools write in place.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Modification time and size of one file; `None` while it is missing.
+type Stamp = Option<(SystemTime, u64)>;
+
+/// Polls a set of files and reports a change only once their modification
+/// times and sizes have held still for a whole poll. A `cp` or `rsync`
+/// without a temporary file writes the feed in place, and a truncated CSV
+/// still parses, so acting on the first changed stamp would load a partial
+/// feed.
+#[derive(Debug, Clone)]
+pub struct FeedWatcher {
+    paths: Vec<PathBuf>,
+    /// Stamps as of the last load.
+    loaded: Vec<Stamp>,
+    /// Changed stamps seen on the previous poll, waiting to settle.
+    pending: Option<Vec<Stamp>>,
+}
+
+impl FeedWatcher {
+    /// Takes the files as they are now to be the loaded version.
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let loaded = stamps(&paths);
+        Self {
+            paths,
+            loaded,
+            pending: None,
+        }
+    }
+
+    /// Whether the files changed since the last load and have stopped
+    /// changing since the previous poll. Missing files count as still being
+    /// written.
+    pub fn poll(&mut self) -> bool {
+        let current = stamps(&self.paths);
+        if current.iter().any(Option::is_none) || current == self.loaded {
+            self.pending = None;
+            return false;
+        }
+        if self.pending.as_ref() == Some(&current) {
+            self.pending = None;
+            self.loaded = current;
+            return true;
+        }
+        self.pending = Some(current);
+        false
+    }
+
+    /// Records the files as they are now as loaded, e.g. after a reload
+    /// triggered some other way.
+    pub fn mark_loaded(&mut self) {
+        self.loaded = stamps(&self.paths);
+        self.pending = None;
+    }
+}
+
+fn stamps(paths: &[PathBuf]) -> Vec<Stamp> {
+    paths.iter().map(|path| stamp(path)).collect()
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
@@ -0,0 +1,269 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use feed_sync::{decode_public_key, Feed, FeedError, FeedUpdater, Update};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// Request path and lower-cased headers.
+type Request = (String, HashMap<String, String>);
+
+#[derive(Clone)]
+struct Resource {
+    body: Vec<u8>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Minimal HTTP/1.1 mirror stand-in that honours `If-None-Match` and
+/// `If-Modified-Since` and records the headers of every request.
+#[derive(Clone, Default)]
+struct Mirror {
+    resources: Arc<Mutex<HashMap<String, Resource>>>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Mirror {
+    async fn start(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mirror = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mirror = mirror.clone();
+                tokio::spawn(async move { mirror.serve(stream).await });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn publish(&self, path: &str, resource: Resource) {
+        self.resources
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), resource);
+    }
+
+    fn last_request(&self, path: &str) -> HashMap<String, String> {
+        let requests = self.requests.lock().unwrap();
+        let (_, headers) = requests.iter().rev().find(|(p, _)| p == path).unwrap();
+        headers.clone()
+    }
+
+    async fn serve(&self, stream: tokio::net::TcpStream) {
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        let path = line.split_whitespace().nth(1).unwrap().to_string();
+        let mut headers = HashMap::new();
+        loop {
+            line.clear();
+            stream.read_line(&mut line).await.unwrap();
+            match line.trim_end().split_once(": ") {
+                Some((name, value)) => {
+                    headers.insert(name.to_ascii_lowercase(), value.to_string());
+                }
+                None => break,
+            }
+        }
+        self.requests
+            .lock()
+            .unwrap()
+            .push((path.clone(), headers.clone()));
+
+        let resource = self.resources.lock().unwrap().get(&path).cloned();
+        let response = match resource {
+            None => b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n".to_vec(),
+            Some(resource) => {
+                let unchanged = resource.etag.is_some()
+                    && headers.get("if-none-match") == resource.etag.as_ref()
+                    || resource.last_modified.is_some()
+                        && headers.get("if-modified-since") == resource.last_modified.as_ref();
+                let mut head = if unchanged {
+                    "HTTP/1.1 304 Not Modified\r\n".to_string()
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n",
+                        resource.body.len()
+                    )
+                };
+                if let Some(etag) = &resource.etag {
+                    head.push_str(&format!("etag: {}\r\n", etag));
+                }
+                if let Some(last_modified) = &resource.last_modified {
+                    head.push_str(&format!("last-modified: {}\r\n", last_modified));
+                }
+                head.push_str("\r\n");
+                let mut response = head.into_bytes();
+                if !unchanged {
+                    response.extend_from_slice(&resource.body);
+                }
+                response
+            }
+        };
+        stream.get_mut().write_all(&response).await.unwrap();
+        stream.get_mut().shutdown().await.unwrap();
+    }
+}
+
+fn key_pair() -> Ed25519KeyPair {
+    Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap()
+}
+
+fn updater() -> FeedUpdater {
+    let public_key = BASE64.encode(key_pair().public_key().as_ref());
+    FeedUpdater::new(decode_public_key(&public_key).unwrap())
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("feed-sync-{}-{}", std::process::id(), name))
+}
+
+fn resource(body: &[u8], etag: Option<&str>, last_modified: Option<&str>) -> Resource {
+    Resource {
+        body: body.to_vec(),
+        etag: etag.map(String::from),
+        last_modified: last_modified.map(String::from),
+    }
+}
+
+fn signature(body: &[u8]) -> Resource {
+    resource(key_pair().sign(body).as_ref(), None, None)
+}
+
+fn cleanup(path: &Path) {
+    for suffix in ["", ".meta", ".download"] {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        let _ = std::fs::remove_file(PathBuf::from(name));
+    }
+}
+
+#[tokio::test]
+async fn verified_feed_is_installed_and_then_skipped_while_unchanged() {
+    let mirror = Mirror::default();
+    let base = mirror.start().await;
+    let body = b"cidr,asn,provider\n10.0.0.0/8,64500,Example VPN\n";
+    mirror.publish("/vpn.csv", resource(body, Some("\"v1\""), None));
+    mirror.publish("/vpn.csv.sig", signature(body));
+
+    let path = temp_path("etag.csv");
+    let feed = Feed::new(&format!("{}/vpn.csv", base), None, &path).unwrap();
+    let updater = updater();
+
+    let update = updater.update(&feed).await.unwrap();
+    assert_eq!(
+        update,
+        Update::Updated {
+            bytes: body.len() as u64
+        }
+    );
+    assert_eq!(std::fs::read(&path).unwrap(), body);
+    assert!(!mirror
+        .last_request("/vpn.csv")
+        .contains_key("if-none-match"));
+
+    assert_eq!(updater.update(&feed).await.unwrap(), Update::Unchanged);
+    assert_eq!(
+        mirror
+            .last_request("/vpn.csv")
+            .get("if-none-match")
+            .map(String::as_str),
+        Some("\"v1\"")
+    );
+
+    cleanup(&path);
+}
+
+#[tokio::test]
+async fn last_modified_is_sent_back_and_base64_signatures_are_accepted() {
+    let mirror = Mirror::default();
+    let base = mirror.start().await;
+    let date = "Wed, 21 Oct 2026 07:28:00 GMT";
+    let body = b"192.0.2.0/24\n";
+    mirror.publish("/list.txt", resource(body, None, Some(date)));
+    let encoded = BASE64.encode(key_pair().sign(body).as_ref()) + "\n";
+    mirror.publish("/sigs/list.txt", resource(encoded.as_bytes(), None, None));
+
+    let path = temp_path("modified.txt");
+    let feed = Feed::new(
+        &format!("{}/list.txt", base),
+        Some(&format!("{}/sigs/list.txt", base)),
+        &path,
+    )
+    .unwrap();
+    let updater = updater();
+
+    assert!(matches!(
+        updater.update(&feed).await.unwrap(),
+        Update::Updated { .. }
+    ));
+    assert_eq!(updater.update(&feed).await.unwrap(), Update::Unchanged);
+    assert_eq!(
+        mirror
+            .last_request("/list.txt")
+            .get("if-modified-since")
+            .map(String::as_str),
+        Some(date)
+    );
+
+    cleanup(&path);
+}
+
+#[tokio::test]
+async fn bad_signature_keeps_the_last_good_copy() {
+    let mirror = Mirror::default();
+    let base = mirror.start().await;
+    let good = b"cidr,asn,provider\n10.0.0.0/8,64500,Example VPN\n";
+    mirror.publish("/vpn.csv", resource(good, Some("\"v1\""), None));
+    mirror.publish("/vpn.csv.sig", signature(good));
+
+    let path = temp_path("tampered.csv");
+    let feed = Feed::new(&format!("{}/vpn.csv", base), None, &path).unwrap();
+    let updater = updater();
+    updater.update(&feed).await.unwrap();
+
+    let tampered = b"cidr,asn,provider\n0.0.0.0/0,64500,Everything\n";
+    mirror.publish("/vpn.csv", resource(tampered, Some("\"v2\""), None));
+    let err = updater.update(&feed).await.unwrap_err();
+    assert!(matches!(err, FeedError::InvalidSignature(_)), "{}", err);
+    assert_eq!(std::fs::read(&path).unwrap(), good);
+
+    // The validators still describe the good copy, so the next attempt
+    // downloads again instead of trusting the rejected version.
+    mirror.publish("/vpn.csv.sig", signature(tampered));
+    assert!(matches!(
+        updater.update(&feed).await.unwrap(),
+        Update::Updated { .. }
+    ));
+    assert_eq!(std::fs::read(&path).unwrap(), tampered);
+
+    mirror.publish("/vpn.csv", resource(good, Some("\"v3\""), None));
+    mirror.resources.lock().unwrap().remove("/vpn.csv.sig");
+    let err = updater.update(&feed).await.unwrap_err();
+    assert!(
+        matches!(err, FeedError::UnexpectedStatus { status: 404, .. }),
+        "{}",
+        err
+    );
+    assert_eq!(std::fs::read(&path).unwrap(), tampered);
+
+    cleanup(&path);
+}
+
+#[test]
+fn public_keys_must_be_32_bytes() {
+    assert!(decode_public_key(&BASE64.encode([1; 32])).is_ok());
+    assert!(matches!(
+        decode_public_key(&BASE64.encode([1; 31])),
+        Err(FeedError::InvalidPublicKey)
+    ));
+    assert!(matches!(
+        decode_public_key("not base64"),
+        Err(FeedError::InvalidPublicKey)
+    ));
+}
@@ -0,0 +1,58 @@
+use feed_sync::FeedWatcher;
+use std::io::Write;
+use std::path::PathBuf;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("feed-sync-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn feed_written_in_place_is_reported_once_it_holds_still() {
+    let path = temp_path("watch.csv");
+    std::fs::write(
+        &path,
+        "cidr,asn,provider,category\n10.0.0.0/8,64500,A,vpn\n",
+    )
+    .unwrap();
+    let mut watcher = FeedWatcher::new(vec![path.clone()]);
+    assert!(!watcher.poll());
+
+    // A copy in progress: truncated, then the first half written.
+    let mut file = std::fs::File::create(&path).unwrap();
+    file.write_all(b"cidr,asn,provider,category\n10.1.0.0/16,")
+        .unwrap();
+    file.flush().unwrap();
+    assert!(!watcher.poll());
+
+    file.write_all(b"64501,B,vpn\n10.2.0.0/16,64502,C,proxy\n")
+        .unwrap();
+    file.flush().unwrap();
+    drop(file);
+    assert!(!watcher.poll());
+
+    // Unchanged over a whole poll, so the copy is done.
+    assert!(watcher.poll());
+    assert!(!watcher.poll());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn missing_files_and_loads_done_elsewhere_are_not_reported() {
+    let path = temp_path("watch-missing.csv");
+    std::fs::write(&path, "10.0.0.0/8\n").unwrap();
+    let mut watcher = FeedWatcher::new(vec![path.clone()]);
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(!watcher.poll());
+    assert!(!watcher.poll());
+
+    std::fs::write(&path, "10.0.0.0/8\n10.1.0.0/16\n").unwrap();
+    assert!(!watcher.poll());
+    // A reload triggered some other way already picked the change up.
+    watcher.mark_loaded();
+    assert!(!watcher.poll());
+    assert!(!watcher.poll());
+
+    std::fs::remove_file(path).unwrap();
+}
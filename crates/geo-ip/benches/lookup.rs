@@ -0,0 +1,76 @@
+use cidr_utils::cidr::IpCidr;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use geo_ip::IpTrie;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Small deterministic generator so runs are comparable without pulling in `rand`.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0
+    }
+}
+
+fn ranges(count: usize) -> Vec<IpCidr> {
+    let mut rng = Lcg(0x5eed);
+    (0..count)
+        .map(|i| {
+            if i % 4 == 3 {
+                let addr = Ipv6Addr::from((rng.next() as u128) << 80);
+                IpCidr::new(IpAddr::V6(addr), 48).unwrap()
+            } else {
+                let addr = Ipv4Addr::from((rng.next() as u32) & 0xffff_ff00);
+                IpCidr::new(IpAddr::V4(addr), 24).unwrap()
+            }
+        })
+        .collect()
+}
+
+fn probes(count: usize) -> Vec<IpAddr> {
+    let mut rng = Lcg(0xfeed);
+    (0..count)
+        .map(|_| IpAddr::V4(Ipv4Addr::from(rng.next() as u32)))
+        .collect()
+}
+
+fn linear_scan(entries: &[IpCidr], ip: IpAddr) -> Option<usize> {
+    entries.iter().position(|cidr| cidr.contains(&ip))
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let probes = probes(256);
+    let mut group = c.benchmark_group("lookup");
+
+    for size in [1_000, 10_000, 100_000] {
+        let entries = ranges(size);
+        let mut trie = IpTrie::new();
+        for (i, cidr) in entries.iter().enumerate() {
+            trie.insert(cidr, i);
+        }
+
+        group.bench_with_input(BenchmarkId::new("linear_scan", size), &entries, |b, e| {
+            b.iter(|| {
+                for &ip in &probes {
+                    black_box(linear_scan(e, ip));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("trie", size), &trie, |b, t| {
+            b.iter(|| {
+                for &ip in &probes {
+                    black_box(t.longest_match(ip));
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_lookup);
+criterion_main!(benches);
@@ -0,0 +1,82 @@
+use std::fmt;
+use std::iter;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// How the address a range matched relates to the address looked up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AddressForm {
+    /// The address as given.
+    #[default]
+    Direct,
+    /// The IPv4 address of an IPv4-mapped IPv6 address (`::ffff:a.b.c.d`).
+    Ipv4Mapped,
+    /// The IPv4 address of a deprecated IPv4-compatible IPv6 address
+    /// (`::a.b.c.d`).
+    Ipv4Compatible,
+    /// The IPv4 address of a 6to4 site (`2002:aabb:ccdd::/48`).
+    SixToFour,
+    /// The Teredo client's public IPv4 address (`2001::/32`).
+    Teredo,
+    /// The IPv4 address behind a well-known-prefix NAT64 address
+    /// (`64:ff9b::/96`).
+    Nat64,
+}
+
+impl AddressForm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AddressForm::Direct => "direct",
+            AddressForm::Ipv4Mapped => "ipv4_mapped",
+            AddressForm::Ipv4Compatible => "ipv4_compatible",
+            AddressForm::SixToFour => "6to4",
+            AddressForm::Teredo => "teredo",
+            AddressForm::Nat64 => "nat64",
+        }
+    }
+}
+
+impl fmt::Display for AddressForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The addresses to check for `ip`, in order.
+///
+/// IPv4-mapped and IPv4-compatible addresses are IPv4 addresses in IPv6
+/// notation and are checked only as IPv4. 6to4, Teredo and NAT64 addresses
+/// are checked as given first, then as the IPv4 address they embed.
+pub(crate) fn forms(ip: IpAddr) -> impl Iterator<Item = (IpAddr, AddressForm)> {
+    let IpAddr::V6(v6) = ip else {
+        return iter::once((ip, AddressForm::Direct)).chain(None);
+    };
+    if let Some((v4, form)) = unwrap_ipv4(v6) {
+        return iter::once((IpAddr::V4(v4), form)).chain(None);
+    }
+    let embedded = embedded_ipv4(v6).map(|(v4, form)| (IpAddr::V4(v4), form));
+    iter::once((ip, AddressForm::Direct)).chain(embedded)
+}
+
+fn unwrap_ipv4(ip: Ipv6Addr) -> Option<(Ipv4Addr, AddressForm)> {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return Some((v4, AddressForm::Ipv4Mapped));
+    }
+    let bits = u128::from(ip);
+    // `::` and `::1` share the prefix but are not IPv4 addresses.
+    (bits >> 32 == 0 && bits > 1)
+        .then(|| (Ipv4Addr::from(bits as u32), AddressForm::Ipv4Compatible))
+}
+
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<(Ipv4Addr, AddressForm)> {
+    let bits = u128::from(ip);
+    if bits >> 112 == 0x2002 {
+        Some((Ipv4Addr::from((bits >> 80) as u32), AddressForm::SixToFour))
+    } else if bits >> 96 == 0x2001_0000 {
+        // The client address is stored with every bit inverted.
+        Some((Ipv4Addr::from(!(bits as u32)), AddressForm::Teredo))
+    } else if bits >> 32 == 0x0064_ff9b_0000_0000_0000_0000 {
+        Some((Ipv4Addr::from(bits as u32), AddressForm::Nat64))
+    } else {
+        None
+    }
+}
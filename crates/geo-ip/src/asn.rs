@@ -0,0 +1,186 @@
+use crate::trie::network;
+use crate::{GeoIpError, IpTrie};
+use cidr_utils::cidr::IpCidr;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// What kind of network an autonomous system operates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AsnType {
+    Hosting,
+    Isp,
+    Mobile,
+    Education,
+    VpnProvider,
+}
+
+impl AsnType {
+    pub const ALL: [AsnType; 5] = [
+        AsnType::Hosting,
+        AsnType::Isp,
+        AsnType::Mobile,
+        AsnType::Education,
+        AsnType::VpnProvider,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AsnType::Hosting => "hosting",
+            AsnType::Isp => "isp",
+            AsnType::Mobile => "mobile",
+            AsnType::Education => "education",
+            AsnType::VpnProvider => "vpn_provider",
+        }
+    }
+}
+
+impl fmt::Display for AsnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AsnType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_ascii_lowercase().replace('-', "_");
+        AsnType::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == normalized)
+            .ok_or_else(|| s.to_string())
+    }
+}
+
+/// ASN-level facts about an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsnMatch {
+    pub asn: u32,
+    /// Announced prefix the ASN came from, when the prefix-to-ASN mapping
+    /// knew the address.
+    pub prefix: Option<IpCidr>,
+    /// Organisation and type from the ASN table, if the ASN is classified.
+    pub organization: Option<Arc<str>>,
+    pub kind: Option<AsnType>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AsnRecord {
+    asn: String,
+    organization: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Debug, Clone)]
+struct AsnClass {
+    organization: Arc<str>,
+    kind: AsnType,
+}
+
+/// ASN classification table plus the prefix-to-ASN mapping used to find an
+/// address's ASN.
+#[derive(Debug, Default)]
+pub(crate) struct AsnDatabase {
+    classes: HashMap<u32, AsnClass>,
+    prefixes: IpTrie<u32>,
+}
+
+impl AsnDatabase {
+    /// Replaces the classification table with an `asn,organization,type` CSV.
+    pub(crate) fn load_table(&mut self, path: &Path) -> Result<usize, GeoIpError> {
+        let mut rdr = csv::Reader::from_path(path)?;
+        let mut classes = HashMap::new();
+
+        for result in rdr.deserialize() {
+            let record: AsnRecord = result?;
+            let asn = parse_asn(&record.asn).ok_or(GeoIpError::InvalidAsn(record.asn))?;
+            let kind = record.kind.parse().map_err(GeoIpError::InvalidAsnType)?;
+            classes.insert(
+                asn,
+                AsnClass {
+                    organization: record.organization.into(),
+                    kind,
+                },
+            );
+        }
+
+        self.classes = classes;
+        Ok(self.classes.len())
+    }
+
+    /// Replaces the prefix mapping with a routeviews `pfx2as` dump: one
+    /// `prefix<TAB>length<TAB>asn` line per announcement. Multi-origin
+    /// (`64500_64501`) and AS-set (`64500,64501`) origins map to the first ASN.
+    pub(crate) fn load_prefixes<R: BufRead>(&mut self, reader: R) -> Result<usize, GeoIpError> {
+        let mut prefixes = IpTrie::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| GeoIpError::InvalidPrefixToAsn {
+                line: number + 1,
+                reason: reason.to_string(),
+            };
+
+            let mut fields = line.split_whitespace();
+            let (Some(prefix), Some(len), Some(origin)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid("expected prefix, length and ASN"));
+            };
+            let addr: IpAddr = prefix.parse().map_err(|_| invalid("invalid prefix"))?;
+            let len: u8 = len.parse().map_err(|_| invalid("invalid prefix length"))?;
+            let cidr = IpCidr::new(addr, len).map_err(|_| invalid("invalid prefix"))?;
+            let asn = origin
+                .split(['_', ','])
+                .next()
+                .and_then(parse_asn)
+                .ok_or_else(|| invalid("invalid origin ASN"))?;
+
+            prefixes.insert(&cidr, asn);
+        }
+
+        self.prefixes = prefixes;
+        Ok(self.prefixes.len())
+    }
+
+    /// Resolves `ip` to an ASN through the prefix mapping, falling back to
+    /// `fallback_asn` (e.g. from the range table) when no prefix covers it.
+    pub(crate) fn lookup(
+        &self,
+        ip: IpAddr,
+        fallback_asn: impl FnOnce() -> Option<u32>,
+    ) -> Option<AsnMatch> {
+        let (asn, prefix) = match self.prefixes.longest_match_with_len(ip) {
+            Some((&asn, len)) => (asn, network(ip, len)),
+            None => (fallback_asn().filter(|&asn| asn != 0)?, None),
+        };
+        let class = self.classes.get(&asn);
+        Some(AsnMatch {
+            asn,
+            prefix,
+            organization: class.map(|class| Arc::clone(&class.organization)),
+            kind: class.map(|class| class.kind),
+        })
+    }
+}
+
+/// Accepts `64500` and `AS64500`, the prefix in any case.
+pub(crate) fn parse_asn(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let digits = match value.get(..2) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("as") => &value[2..],
+        _ => value,
+    };
+    digits.parse().ok()
+}
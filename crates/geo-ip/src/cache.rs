@@ -0,0 +1,122 @@
+use lru::LruCache;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+const SHARDS: usize = 16;
+
+/// Counters of a lookup cache since its database was loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to make room for new ones. Invalidation after the
+    /// database changes is not counted.
+    pub evictions: u64,
+    /// Entries currently cached.
+    pub len: usize,
+    /// Entries the cache can hold: the configured size rounded up to an
+    /// equal share per shard.
+    pub capacity: usize,
+}
+
+impl CacheStats {
+    /// Share of lookups answered from the cache, `0.0` before any lookup.
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// LRU cache split into independently locked shards.
+///
+/// Each key always lands in the same shard, so concurrent lookups only
+/// contend when they hash to the same shard, and the lock is held just for
+/// the map operation itself.
+#[derive(Debug)]
+pub(crate) struct ShardedLru<K: Hash + Eq, V> {
+    shards: Box<[Mutex<LruCache<K, V>>]>,
+    hasher: RandomState,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl<K: Hash + Eq, V: Clone> ShardedLru<K, V> {
+    /// Creates a cache holding at least `capacity` entries in total, rounded
+    /// up to an equal share per shard.
+    pub(crate) fn new(capacity: NonZeroUsize) -> Self {
+        let per_shard =
+            NonZeroUsize::new(capacity.get().div_ceil(SHARDS)).expect("Cache size must be > 0");
+        Self {
+            shards: (0..SHARDS)
+                .map(|_| Mutex::new(LruCache::new(per_shard)))
+                .collect(),
+            hasher: RandomState::new(),
+            capacity: per_shard.get() * SHARDS,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    fn shard(&self, key: &K) -> &Mutex<LruCache<K, V>> {
+        &self.shards[self.hasher.hash_one(key) as usize % SHARDS]
+    }
+
+    pub(crate) fn get(&self, key: &K) -> Option<V> {
+        // A panic elsewhere cannot leave an LruCache half-updated in a way that
+        // matters for lookups, so a poisoned shard is still usable.
+        let mut shard = self
+            .shard(key)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let value = shard.get(key).cloned();
+        let counter = if value.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    pub(crate) fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.lock().unwrap_or_else(PoisonError::into_inner).clear();
+        }
+    }
+
+    pub(crate) fn put(&self, key: K, value: V) {
+        let mut shard = self
+            .shard(&key)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let evicts = shard.len() == shard.cap().get() && !shard.contains(&key);
+        shard.put(key, value);
+        if evicts {
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            len: self
+                .shards
+                .iter()
+                .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner).len())
+                .sum(),
+            capacity: self.capacity,
+        }
+    }
+}
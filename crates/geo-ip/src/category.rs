@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Kind of anonymizing service a range belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// Commercial VPN egress.
+    Vpn,
+    /// Tor exit relay.
+    Tor,
+    /// Open or public proxy.
+    Proxy,
+    /// Proxy service routing through residential ISP addresses.
+    ResidentialProxy,
+    /// Datacenter or hosting range that is not known to be a VPN.
+    Hosting,
+}
+
+impl Category {
+    pub const ALL: [Category; 5] = [
+        Category::Vpn,
+        Category::Tor,
+        Category::Proxy,
+        Category::ResidentialProxy,
+        Category::Hosting,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Vpn => "vpn",
+            Category::Tor => "tor",
+            Category::Proxy => "proxy",
+            Category::ResidentialProxy => "residential_proxy",
+            Category::Hosting => "hosting",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Category::ALL
+            .into_iter()
+            .find(|c| c.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| s.to_string())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::GeoIpError;
+use flate2::read::MultiGzDecoder;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// How an input file is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Picks the codec from a `.gz`/`.gzip` or `.zst`/`.zstd` extension,
+    /// falling back to the file's leading magic bytes.
+    pub(crate) fn detect(path: &Path, head: &[u8]) -> Self {
+        Self::from_extension(path).unwrap_or(if head.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if head.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        })
+    }
+
+    fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_ascii_lowercase();
+        match extension.as_str() {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// The file name of `path` without its compression extension, if any, and
+/// the extension before it: `proxies.netset.gz` gives `proxies`.
+pub(crate) fn file_stem(path: &Path) -> Option<&OsStr> {
+    let stem = path.file_stem()?;
+    match Compression::from_extension(path) {
+        Some(_) => Path::new(stem).file_stem(),
+        None => Some(stem),
+    }
+}
+
+/// Opens `path` for reading, decompressing gzip and zstd input on the fly.
+pub(crate) fn open(path: &Path) -> Result<Box<dyn BufRead>, GeoIpError> {
+    let mut file = BufReader::new(File::open(path)?);
+    let compression = Compression::detect(path, file.fill_buf()?);
+    Ok(match compression {
+        Compression::None => Box::new(file),
+        // Concatenated members are valid gzip; `gzip -c a b > feed.gz` is
+        // how some mirrors append to a feed.
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(file)?)),
+    })
+}
+
+/// Reads the decompressed contents of `path` in full.
+pub(crate) fn read(path: &Path) -> Result<Vec<u8>, GeoIpError> {
+    let mut bytes = Vec::new();
+    open(path)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
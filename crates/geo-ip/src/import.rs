@@ -0,0 +1,205 @@
+use crate::GeoIpError;
+use cidr_utils::cidr::IpCidr;
+use std::fmt;
+use std::io::BufRead;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Address list formats that carry no ASN or provider columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListFormat {
+    /// One network or address per line.
+    Cidr,
+    /// One `start-end` range per line; a bare network or address also works.
+    Range,
+    /// `ipset save` output; only `add` lines are read.
+    Ipset,
+    /// `nft list set` output; addresses are taken from `elements = { ... }`.
+    Nftables,
+}
+
+impl ListFormat {
+    pub const ALL: [ListFormat; 4] = [
+        ListFormat::Cidr,
+        ListFormat::Range,
+        ListFormat::Ipset,
+        ListFormat::Nftables,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListFormat::Cidr => "cidr",
+            ListFormat::Range => "range",
+            ListFormat::Ipset => "ipset",
+            ListFormat::Nftables => "nftables",
+        }
+    }
+}
+
+impl fmt::Display for ListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ListFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        ListFormat::ALL
+            .into_iter()
+            .find(|format| format.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| s.to_string())
+    }
+}
+
+/// Networks parsed from one list element, or why it could not be parsed.
+pub(crate) struct ListItem {
+    /// 1-based line the element appeared on.
+    pub(crate) row: u64,
+    pub(crate) cidrs: Result<Vec<IpCidr>, String>,
+}
+
+pub(crate) fn parse<R: BufRead>(
+    reader: R,
+    format: ListFormat,
+) -> Result<Vec<ListItem>, GeoIpError> {
+    let mut items = Vec::new();
+    let mut in_elements = false;
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let row = number as u64 + 1;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match format {
+            ListFormat::Cidr => items.push(ListItem {
+                row,
+                cidrs: parse_network(line).map(|cidr| vec![cidr]),
+            }),
+            ListFormat::Range => items.push(ListItem {
+                row,
+                cidrs: parse_element(line),
+            }),
+            ListFormat::Ipset => {
+                let mut fields = line.split_whitespace();
+                if fields.next() != Some("add") {
+                    continue;
+                }
+                // `add <set> <element> [timeout N] [comment "..."] ...`
+                let cidrs = match fields.nth(1) {
+                    Some(element) => parse_element(element),
+                    None => Err("add without an element".to_string()),
+                };
+                items.push(ListItem { row, cidrs });
+            }
+            ListFormat::Nftables => {
+                let mut rest = line;
+                if !in_elements {
+                    let Some(start) = line.find("elements") else {
+                        continue;
+                    };
+                    let Some(open) = line[start..].find('{') else {
+                        continue;
+                    };
+                    rest = &line[start + open + 1..];
+                    in_elements = true;
+                }
+                if let Some(close) = rest.find('}') {
+                    rest = &rest[..close];
+                    in_elements = false;
+                }
+                for element in rest.split(',').map(str::trim) {
+                    // Elements may carry `timeout`/`expires` annotations.
+                    let Some(element) = element.split_whitespace().next() else {
+                        continue;
+                    };
+                    items.push(ListItem {
+                        row,
+                        cidrs: parse_element(element),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(items)
+}
+
+/// Parses a network, a bare address, or a `start-end` range.
+fn parse_element(element: &str) -> Result<Vec<IpCidr>, String> {
+    match element.split_once('-') {
+        Some((start, end)) => {
+            let start = parse_addr(start)?;
+            let end = parse_addr(end)?;
+            range_to_cidrs(start, end)
+        }
+        None => parse_network(element).map(|cidr| vec![cidr]),
+    }
+}
+
+fn parse_network(network: &str) -> Result<IpCidr, String> {
+    let network = network.trim();
+    if network.contains('/') {
+        network
+            .parse()
+            .map_err(|_| format!("invalid network: {}", network))
+    } else {
+        parse_addr(network).map(IpCidr::new_host)
+    }
+}
+
+fn parse_addr(addr: &str) -> Result<IpAddr, String> {
+    let addr = addr.trim();
+    addr.parse()
+        .map_err(|_| format!("invalid address: {}", addr))
+}
+
+/// Splits the inclusive range `start..=end` into the fewest covering networks.
+pub(crate) fn range_to_cidrs(start: IpAddr, end: IpAddr) -> Result<Vec<IpCidr>, String> {
+    let (start_bits, end_bits, width) = match (start, end) {
+        (IpAddr::V4(s), IpAddr::V4(e)) => (u32::from(s) as u128, u32::from(e) as u128, 32),
+        (IpAddr::V6(s), IpAddr::V6(e)) => (u128::from(s), u128::from(e), 128),
+        _ => return Err(format!("range mixes address families: {}-{}", start, end)),
+    };
+    if start_bits > end_bits {
+        return Err(format!("range start after end: {}-{}", start, end));
+    }
+
+    let mut cidrs = Vec::new();
+    let mut block_start = start_bits;
+    loop {
+        let mut host_bits = if block_start == 0 {
+            width
+        } else {
+            (block_start.trailing_zeros() as u8).min(width)
+        };
+        while host_bits > 0 && end_bits - block_start < last_offset(host_bits) {
+            host_bits -= 1;
+        }
+
+        let addr = match start {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from(block_start as u32)),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from(block_start)),
+        };
+        cidrs.push(IpCidr::new(addr, width - host_bits).expect("aligned network"));
+
+        let block_end = block_start + last_offset(host_bits);
+        if block_end >= end_bits {
+            return Ok(cidrs);
+        }
+        block_start = block_end + 1;
+    }
+}
+
+fn last_offset(host_bits: u8) -> u128 {
+    if host_bits >= 128 {
+        u128::MAX
+    } else {
+        (1 << host_bits) - 1
+    }
+}
@@ -1,10 +1,44 @@
+use asn::AsnDatabase;
 use cidr_utils::cidr::IpCidr;
-use lru::LruCache;
-use serde::Deserialize;
+use mmdb::{GeoDatabase, MmdbDatabase};
+use overrides::OverrideList;
+use std::io::{Cursor, Read};
 use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use std::{net::IpAddr, path::Path};
+use table::{IpEntry, RangeTable};
 use thiserror::Error;
 
+mod address;
+mod asn;
+mod cache;
+mod category;
+mod compression;
+mod import;
+mod location;
+mod mmdb;
+mod overrides;
+mod report;
+mod snapshot;
+mod source;
+mod table;
+mod tor;
+pub mod trie;
+
+pub use address::AddressForm;
+pub use asn::{AsnMatch, AsnType};
+pub use cache::CacheStats;
+pub use category::Category;
+pub use import::ListFormat;
+pub use location::{Coordinates, Location};
+pub use mmdb::MmdbPaths;
+pub use overrides::{Override, Verdict};
+pub use report::{LoadMode, LoadReport, Overlap, SkippedRow};
+pub use snapshot::SnapshotMetadata;
+pub use source::{SourceMatch, SourceOptions};
+pub use trie::IpTrie;
+
 #[derive(Error, Debug)]
 pub enum GeoIpError {
     #[error("CSV parsing error: {0}")]
@@ -15,70 +49,406 @@ pub enum GeoIpError {
     InvalidCidr(String),
     #[error("Invalid ASN format in record: {0}")]
     InvalidAsn(String),
+    #[error("Invalid category in record: {0}")]
+    InvalidCategory(String),
+    #[error("Invalid ASN type in record: {0}")]
+    InvalidAsnType(String),
+    #[error("Invalid prefix-to-ASN mapping at line {line}: {reason}")]
+    InvalidPrefixToAsn { line: usize, reason: String },
+    #[error("MaxMind DB error: {0}")]
+    MmdbError(#[from] maxminddb::MaxMindDBError),
+    #[error("No MaxMind database path configured")]
+    NoMmdbConfigured,
+    #[error("Invalid Tor exit list at line {line}: {reason}")]
+    InvalidTorExitList { line: usize, reason: String },
+    #[error("Invalid override verdict in record: {0}")]
+    InvalidVerdict(String),
+    #[error("Invalid timestamp in record: {0}")]
+    InvalidTimestamp(String),
+    #[error("Invalid confidence in record, expected 0.0 to 1.0: {0}")]
+    InvalidConfidence(String),
+    #[error("Invalid country code in record, expected ISO 3166-1 alpha-2: {0}")]
+    InvalidCountry(String),
+    #[error("Invalid coordinates in record, expected latitude and longitude: {0}")]
+    InvalidCoordinates(String),
+    #[error("Invalid list entry at line {line}: {reason}")]
+    InvalidListEntry { line: u64, reason: String },
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedSnapshotVersion(u32),
 }
 
-#[derive(Debug, Deserialize)]
-struct CsvRecord {
-    cidr: String,
-    asn: String,
-    provider: String,
+/// The database range an address was found in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpMatch {
+    /// Most specific network containing the address.
+    pub cidr: IpCidr,
+    /// Address the network contains: the one looked up, or the IPv4 address
+    /// it carries as described by `form`.
+    pub address: IpAddr,
+    pub form: AddressForm,
+    pub asn: u32,
+    pub provider: Arc<str>,
+    /// Feed the range was loaded from.
+    pub source: Arc<str>,
+    /// ISO 3166-1 alpha-2 country code, when the range or a geo database
+    /// knows it.
+    pub country: Option<Arc<str>>,
+    /// Subdivision such as a state or province.
+    pub region: Option<Arc<str>>,
+    pub coordinates: Option<Coordinates>,
+    /// Anonymizer kind, or `None` when the range is known but not flagged.
+    pub category: Option<Category>,
+    /// Feed-reported trust in this entry, `1.0` when the feed gives none.
+    pub confidence: f32,
+    pub first_seen: Option<SystemTime>,
+    pub last_seen: Option<SystemTime>,
+    /// When the source last published this entry, if it says.
+    pub published: Option<SystemTime>,
+    /// Every source listing the address, this match's source first.
+    pub sources: Vec<SourceMatch>,
 }
 
-#[derive(Debug, Clone)]
-struct IpEntry {
-    cidr: IpCidr,
-    asn: u32,
+impl IpMatch {
+    pub fn is_vpn(&self) -> bool {
+        self.category.is_some()
+    }
 
-    #[allow(dead_code)]
-    provider: String,
+    pub fn location(&self) -> Location {
+        Location {
+            country: self.country.clone(),
+            region: self.region.clone(),
+            coordinates: self.coordinates,
+        }
+    }
+
+    /// Takes `location` unless the range already carries one of its own.
+    fn fill_location(&mut self, location: Location) {
+        if self.location().is_empty() {
+            self.country = location.country;
+            self.region = location.region;
+            self.coordinates = location.coordinates;
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Local ranges, optionally backed by MaxMind databases.
+///
+/// Ranges in the local table (CSV feeds, Tor exits) take precedence; the
+/// MaxMind databases answer for addresses the table does not list.
+#[derive(Debug)]
 pub struct IpDatabase {
-    entries: Vec<IpEntry>,
-    asn_cache: LruCache<IpAddr, u32>,
+    table: RangeTable,
+    mmdb: Option<Box<MmdbDatabase>>,
+    /// City database used only to locate addresses.
+    geo: Option<Box<GeoDatabase>>,
+    overrides: OverrideList,
+    asn: AsnDatabase,
+    snapshot: Option<SnapshotMetadata>,
+}
+
+impl Default for IpDatabase {
+    /// An empty database to merge feeds into.
+    fn default() -> Self {
+        Self {
+            table: RangeTable::new(),
+            mmdb: None,
+            geo: None,
+            overrides: OverrideList::default(),
+            asn: AsnDatabase::default(),
+            snapshot: None,
+        }
+    }
 }
 
 impl IpDatabase {
+    /// Loads a `cidr,asn,provider[,category]` feed, failing on the first
+    /// invalid row. Gzip and zstd feeds are decompressed as they are read.
     pub fn load_from_csv<P: AsRef<Path>>(path: P) -> Result<Self, GeoIpError> {
-        let mut rdr = csv::Reader::from_path(path)?;
-        let mut entries = Vec::new();
-
-        for result in rdr.deserialize() {
-            let record: CsvRecord = result?;
-            let cidr = record
-                .cidr
-                .parse::<IpCidr>()
-                .map_err(|_| GeoIpError::InvalidCidr(record.cidr.clone()))?;
-            let asn: u32 = record
-                .asn
-                .parse()
-                .map_err(|_| GeoIpError::InvalidAsn(record.asn.clone()))?;
-
-            entries.push(IpEntry {
-                cidr,
-                asn,
-                provider: record.provider,
-            });
+        Self::load_from_csv_with_report(path, LoadMode::Strict).map(|(ip_db, _)| ip_db)
+    }
+
+    /// Like [`IpDatabase::load_from_csv`], also reporting skipped, duplicate
+    /// and overlapping rows. In [`LoadMode::Lenient`] invalid rows are
+    /// skipped instead of failing the load.
+    pub fn load_from_csv_with_report<P: AsRef<Path>>(
+        path: P,
+        mode: LoadMode,
+    ) -> Result<(Self, LoadReport), GeoIpError> {
+        let mut ip_db = Self::default();
+        let report = ip_db.merge_csv(path, &SourceOptions::default(), mode)?;
+        Ok((ip_db, report))
+    }
+
+    /// Loads either a CSV feed or a compiled snapshot, telling them apart by
+    /// the snapshot magic bytes. Snapshots yield a report with only `loaded`
+    /// set; `mode` applies to CSV feeds. Either may be gzip or zstd
+    /// compressed.
+    pub fn load_from_file<P: AsRef<Path>>(
+        path: P,
+        mode: LoadMode,
+    ) -> Result<(Self, LoadReport), GeoIpError> {
+        let path = path.as_ref();
+        let mut reader = compression::open(path)?;
+        let mut head = Vec::with_capacity(snapshot::MAGIC.len());
+        (&mut reader)
+            .take(snapshot::MAGIC.len() as u64)
+            .read_to_end(&mut head)?;
+        if !snapshot::is_snapshot(&head) {
+            let mut ip_db = Self::default();
+            let report = ip_db.table.merge_csv(
+                Cursor::new(head).chain(reader),
+                source_name(path),
+                &SourceOptions::default(),
+                mode,
+            )?;
+            return Ok((ip_db, report));
         }
 
+        let mut bytes = head;
+        reader.read_to_end(&mut bytes)?;
+        let ip_db = Self::from_snapshot(&bytes)?;
+        let report = LoadReport {
+            loaded: ip_db.table.entries().len(),
+            ..LoadReport::default()
+        };
+        Ok((ip_db, report))
+    }
+
+    /// Loads a snapshot written by [`IpDatabase::write_snapshot`], verifying
+    /// its version and checksum. The file may be gzip or zstd compressed.
+    pub fn load_snapshot<P: AsRef<Path>>(path: P) -> Result<Self, GeoIpError> {
+        Self::from_snapshot(&compression::read(path.as_ref())?)
+    }
+
+    fn from_snapshot(bytes: &[u8]) -> Result<Self, GeoIpError> {
+        let (table, metadata) = snapshot::decode(bytes)?;
+        Ok(Self {
+            table,
+            snapshot: Some(metadata),
+            ..Self::default()
+        })
+    }
+
+    /// Writes the local range table (CSV feeds and Tor exits) as a binary
+    /// snapshot. MaxMind databases and operator overrides are not included.
+    pub fn write_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), GeoIpError> {
+        std::fs::write(path, snapshot::encode(&self.table))?;
+        Ok(())
+    }
+
+    /// Metadata of the snapshot this database was loaded from, if any.
+    pub fn snapshot_metadata(&self) -> Option<&SnapshotMetadata> {
+        self.snapshot.as_ref()
+    }
+
+    /// Adds the rows of another `cidr,asn,provider[,category]` feed. A row
+    /// for a network this feed already listed replaces it; other feeds'
+    /// entries for the network are kept alongside. Gzip and zstd feeds are
+    /// decompressed as they are read.
+    pub fn merge_csv<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &SourceOptions,
+        mode: LoadMode,
+    ) -> Result<LoadReport, GeoIpError> {
+        let path = path.as_ref();
+        self.table
+            .merge_csv(compression::open(path)?, source_name(path), options, mode)
+    }
+
+    /// Opens local GeoIP2/GeoLite2 databases. At least one path must be set.
+    pub fn load_from_mmdb(paths: &MmdbPaths) -> Result<Self, GeoIpError> {
         Ok(Self {
-            entries,
-            asn_cache: LruCache::new(NonZeroUsize::new(1000).expect("Cache size must be > 0")),
+            mmdb: Some(Box::new(MmdbDatabase::open(paths)?)),
+            ..Self::default()
         })
     }
 
-    pub fn is_vpn_ip(&mut self, ip: IpAddr) -> bool {
-        if let Some(&asn) = self.asn_cache.get(&ip) {
-            return asn != 0;
+    /// Opens a GeoLite2-City (or Country) database used to locate addresses
+    /// whose range carries no location, and addresses no range lists.
+    pub fn load_geo_mmdb<P: AsRef<Path>>(&mut self, path: P) -> Result<(), GeoIpError> {
+        self.geo = Some(Box::new(GeoDatabase::open(path.as_ref())?));
+        Ok(())
+    }
+
+    /// Merges a plain CIDR, `start-end` range, `ipset save` or nftables set
+    /// list, tagging every network with the source's category and the file
+    /// as source. Ranges are split into the fewest covering networks.
+    /// Compressed lists are read like compressed CSV feeds.
+    pub fn merge_list<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        format: ListFormat,
+        options: &SourceOptions,
+        mode: LoadMode,
+    ) -> Result<LoadReport, GeoIpError> {
+        self.table.merge_list(path.as_ref(), format, options, mode)
+    }
+
+    /// Merges a Tor exit list as `tor` entries and returns how many were added.
+    ///
+    /// Accepts the bulk list (one address per line) and the detailed
+    /// `exit-addresses` format. An address stops matching once its
+    /// `Published` time is more than `max_age` in the past, judged at lookup
+    /// time; bulk-list addresses carry no timestamp and never expire.
+    pub fn merge_tor_exit_list<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        max_age: Option<Duration>,
+    ) -> Result<usize, GeoIpError> {
+        let path = path.as_ref();
+        let source: Arc<str> = path.display().to_string().into();
+        let provider: Arc<str> = "Tor".into();
+        let mut merged = 0;
+
+        self.table.clear_cache();
+        for exit in tor::parse(compression::open(path)?)? {
+            self.table.insert(IpEntry {
+                cidr: IpCidr::new_host(exit.ip),
+                asn: 0,
+                provider: Arc::clone(&provider),
+                category: Some(Category::Tor),
+                source: Arc::clone(&source),
+                weight: 1.0,
+                confidence: 1.0,
+                first_seen: None,
+                last_seen: None,
+                published: exit.published,
+                expires: exit.published.zip(max_age).map(|(p, max_age)| p + max_age),
+                country: None,
+                region: None,
+                coordinates: None,
+            });
+            merged += 1;
         }
 
-        for entry in &self.entries {
-            if entry.cidr.contains(&ip) {
-                self.asn_cache.put(ip, entry.asn);
-                return entry.asn != 0;
+        Ok(merged)
+    }
+
+    /// Replaces the operator overrides with the `cidr,verdict,reason,expires`
+    /// CSV at `path` and returns how many were loaded.
+    pub fn load_overrides<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, GeoIpError> {
+        self.overrides = OverrideList::load_csv(path.as_ref())?;
+        Ok(self.overrides.len())
+    }
+
+    /// Replaces the ASN classification table with an `asn,organization,type`
+    /// CSV and returns how many ASNs it classifies.
+    pub fn load_asn_table<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, GeoIpError> {
+        self.asn.load_table(path.as_ref())
+    }
+
+    /// Replaces the prefix-to-ASN mapping with a routeviews `pfx2as` dump,
+    /// plain or gzipped as published, and returns how many prefixes it maps.
+    pub fn load_prefix_to_asn<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, GeoIpError> {
+        self.asn.load_prefixes(compression::open(path.as_ref())?)
+    }
+
+    /// Resizes the range lookup cache, which remembers per address both the
+    /// ranges found and that none were. The cache starts out empty.
+    pub fn set_cache_capacity(&mut self, capacity: NonZeroUsize) {
+        self.table.set_cache_capacity(capacity);
+    }
+
+    /// Hit, miss and eviction counts of the range lookup cache since this
+    /// database was loaded.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.table.cache_stats()
+    }
+
+    /// Resolves the ASN announcing `ip` and its classification, if known.
+    ///
+    /// The prefix-to-ASN mapping is consulted first; without a covering
+    /// prefix the ASN of the matching database range is used instead.
+    pub fn lookup_asn(&self, ip: IpAddr) -> Option<AsnMatch> {
+        let now = SystemTime::now();
+        address::forms(ip).find_map(|(ip, _)| {
+            self.asn.lookup(ip, || {
+                self.lookup_form(ip, now).map(|ip_match| ip_match.asn)
+            })
+        })
+    }
+
+    /// Returns the most specific unexpired operator override covering `ip`.
+    pub fn check_override(&self, ip: IpAddr) -> Option<Override> {
+        self.check_override_at(ip, SystemTime::now())
+    }
+
+    /// Like [`IpDatabase::check_override`], judging expiry as of `now`.
+    pub fn check_override_at(&self, ip: IpAddr, now: SystemTime) -> Option<Override> {
+        address::forms(ip).find_map(|(ip, _)| self.overrides.check(ip, now).cloned())
+    }
+
+    pub fn is_vpn_ip(&self, ip: IpAddr) -> bool {
+        match self.check_override(ip) {
+            Some(operator_override) => operator_override.verdict == Verdict::Flag,
+            None => self.lookup(ip).is_some_and(|m| m.is_vpn()),
+        }
+    }
+
+    /// Every range in the local table (CSV feeds, lists, Tor exits) in
+    /// address order, one per source. Ranges only known to the MaxMind
+    /// databases are not included.
+    pub fn entries(&self) -> impl Iterator<Item = IpMatch> + '_ {
+        self.table.indexed_entries().map(IpEntry::to_match)
+    }
+
+    /// Returns the most specific range containing `ip`, if any, along with
+    /// every other source that lists the address.
+    ///
+    /// IPv4-mapped and IPv4-compatible addresses are looked up as IPv4. For
+    /// 6to4, Teredo and NAT64 addresses the embedded IPv4 address is looked
+    /// up when the address itself is not listed; `form` tells which matched.
+    ///
+    /// A range without location columns is located through the MaxMind City
+    /// database or the geo database, if one is loaded.
+    pub fn lookup(&self, ip: IpAddr) -> Option<IpMatch> {
+        self.lookup_at(ip, SystemTime::now())
+    }
+
+    /// Like [`IpDatabase::lookup`], judging expiry as of `now`.
+    pub fn lookup_at(&self, ip: IpAddr, now: SystemTime) -> Option<IpMatch> {
+        address::forms(ip).find_map(|(address, form)| {
+            let mut ip_match = self.lookup_form(address, now)?;
+            ip_match.address = address;
+            ip_match.form = form;
+            Some(ip_match)
+        })
+    }
+
+    fn lookup_form(&self, ip: IpAddr, now: SystemTime) -> Option<IpMatch> {
+        let mmdb_match = self.mmdb.as_ref().and_then(|mmdb| mmdb.lookup(ip));
+        let mut ip_match = match (self.table.lookup(ip, now), mmdb_match) {
+            (Some(mut table_match), Some(mmdb_match)) => {
+                table_match.fill_location(mmdb_match.location());
+                table_match.sources.extend(mmdb_match.sources);
+                table_match
+            }
+            (table_match, mmdb_match) => table_match.or(mmdb_match)?,
+        };
+        if let Some(geo) = &self.geo {
+            if ip_match.location().is_empty() {
+                ip_match.fill_location(geo.locate(ip).unwrap_or_default());
             }
         }
-        false
+        Some(ip_match)
     }
+
+    /// Where `ip` is located, whether or not any range lists it: the
+    /// matching range's own location first, then the geo database.
+    pub fn locate(&self, ip: IpAddr) -> Option<Location> {
+        self.lookup(ip)
+            .map(|ip_match| ip_match.location())
+            .filter(|location| !location.is_empty())
+            .or_else(|| {
+                let geo = self.geo.as_ref()?;
+                address::forms(ip).find_map(|(ip, _)| geo.locate(ip))
+            })
+    }
+}
+
+fn source_name(path: &Path) -> Arc<str> {
+    path.display().to_string().into()
 }
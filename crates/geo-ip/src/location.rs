@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+/// A point on the globe in decimal degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    /// Returns `None` unless both values are within their valid ranges.
+    pub fn new(latitude: f64, longitude: f64) -> Option<Self> {
+        ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)).then_some(
+            Self {
+                latitude,
+                longitude,
+            },
+        )
+    }
+}
+
+/// Where an address is registered or located.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    /// ISO 3166-1 alpha-2 country code.
+    pub country: Option<Arc<str>>,
+    /// Subdivision code or name, e.g. a state or province.
+    pub region: Option<Arc<str>>,
+    pub coordinates: Option<Coordinates>,
+}
+
+impl Location {
+    pub fn is_empty(&self) -> bool {
+        self.country.is_none() && self.region.is_none() && self.coordinates.is_none()
+    }
+}
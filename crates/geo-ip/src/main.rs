@@ -0,0 +1,404 @@
+use cidr_utils::cidr::IpCidr;
+use geo_ip::{
+    AddressForm, Category, IpDatabase, IpMatch, ListFormat, LoadMode, LoadReport, SourceOptions,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::net::IpAddr;
+use std::process::ExitCode;
+
+const DEFAULT_DB: &str = "assets/vpn_ips.csv";
+const TOP: usize = 20;
+
+const USAGE: &str = "\
+Usage:
+  geo-ip lookup [--db <file>] <ip>
+      Show the ranges and sources that list an address.
+  geo-ip stats [--db <file>]
+      Entries per category, provider and ASN, and address space covered.
+  geo-ip validate [--format F] <file>
+      Check a feed and list every problem; fails if any row is invalid.
+  geo-ip diff <old> <new>
+      Ranges added, removed or reclassified between two databases.
+  geo-ip compile [--lenient] <output> [--format F] [--category C] [--weight W] <feed>...
+      Compile feeds into a binary snapshot. Feeds are `cidr,asn,provider` CSV
+      unless preceded by `--format cidr|range|ipset|nftables`. `--category`
+      (default vpn) applies to entries that do not name one and `--weight`
+      (default 1.0) sets how far a feed is trusted. Options apply to the
+      feeds that follow them.
+
+`--db` defaults to assets/vpn_ips.csv. Databases may be CSV feeds or
+compiled snapshots, and any input may be gzip or zstd compressed.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("lookup") => lookup(&args[1..]),
+        Some("stats") => stats(&args[1..]),
+        Some("validate") => validate(&args[1..]),
+        Some("diff") => diff(&args[1..]),
+        Some("compile") => compile(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Splits `--db <file>` off `args`, returning the database path and the
+/// remaining arguments.
+fn database_arg(args: &[String]) -> Result<(&str, Vec<&str>), Box<dyn Error>> {
+    let mut db = DEFAULT_DB;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => db = args.next().ok_or(USAGE)?,
+            other => rest.push(other),
+        }
+    }
+    Ok((db, rest))
+}
+
+fn open(path: &str) -> Result<IpDatabase, Box<dyn Error>> {
+    let (ip_db, _) = IpDatabase::load_from_file(path, LoadMode::Strict)?;
+    Ok(ip_db)
+}
+
+fn category_name(category: Option<Category>) -> &'static str {
+    category.map_or("unflagged", |category| category.as_str())
+}
+
+fn lookup(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (db, rest) = database_arg(args)?;
+    let [ip] = rest[..] else {
+        return Err(USAGE.into());
+    };
+    let ip: IpAddr = ip
+        .parse()
+        .map_err(|_| format!("invalid IP address: {}", ip))?;
+    let ip_db = open(db)?;
+
+    let Some(ip_match) = ip_db.lookup(ip) else {
+        println!("{}: not listed", ip);
+        return Ok(());
+    };
+    println!("{}: {}", ip, category_name(ip_match.category));
+    if ip_match.form != AddressForm::Direct {
+        println!("  matched     {} ({})", ip_match.address, ip_match.form);
+    }
+    println!("  range       {}", ip_match.cidr);
+    println!("  provider    {} (AS{})", ip_match.provider, ip_match.asn);
+    println!("  confidence  {}", ip_match.confidence);
+    let location = ip_match.location();
+    if !location.is_empty() {
+        let mut place: Vec<&str> = [&location.country, &location.region]
+            .into_iter()
+            .flatten()
+            .map(|name| &**name)
+            .collect();
+        let coordinates = location
+            .coordinates
+            .map(|c| format!("({}, {})", c.latitude, c.longitude));
+        place.extend(coordinates.as_deref());
+        println!("  location    {}", place.join(" "));
+    }
+    if let Some(last_seen) = ip_match.last_seen.or(ip_match.published) {
+        println!(
+            "  last seen   {}",
+            humantime::format_rfc3339_seconds(last_seen)
+        );
+    }
+    println!("  sources:");
+    for source in &ip_match.sources {
+        println!(
+            "    {}  {}  {}  weight {}",
+            source.source,
+            source.cidr,
+            category_name(source.category),
+            source.weight
+        );
+    }
+    Ok(())
+}
+
+fn stats(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (db, rest) = database_arg(args)?;
+    if !rest.is_empty() {
+        return Err(USAGE.into());
+    }
+    let ip_db = open(db)?;
+
+    let mut categories: HashMap<&'static str, usize> = HashMap::new();
+    let mut providers: HashMap<String, usize> = HashMap::new();
+    let mut asns: HashMap<u32, usize> = HashMap::new();
+    let mut networks = Vec::new();
+    for entry in ip_db.entries() {
+        *categories.entry(category_name(entry.category)).or_default() += 1;
+        *providers.entry(entry.provider.to_string()).or_default() += 1;
+        *asns.entry(entry.asn).or_default() += 1;
+        networks.push(entry.cidr);
+    }
+
+    println!("{}: {} entries", db, networks.len());
+    let (v4, v6) = coverage(&networks);
+    println!("IPv4 space covered: {}", percent(v4));
+    println!("IPv6 space covered: {}", percent(v6));
+    print_counts("By category", categories, usize::MAX);
+    print_counts("By provider", providers, TOP);
+    print_counts(
+        "By ASN",
+        asns.into_iter()
+            .map(|(asn, count)| (format!("AS{}", asn), count)),
+        TOP,
+    );
+    Ok(())
+}
+
+/// Share of the IPv4 and IPv6 address space covered by `networks`, counting
+/// nested networks once.
+fn coverage(networks: &[IpCidr]) -> (f64, f64) {
+    let mut sorted: Vec<_> = networks
+        .iter()
+        .map(|cidr| {
+            (
+                cidr.first_address(),
+                cidr.network_length(),
+                cidr.last_address(),
+            )
+        })
+        .collect();
+    sorted.sort();
+
+    let (mut v4, mut v6) = (0.0, 0.0);
+    let mut covered_until: Option<IpAddr> = None;
+    for (first, len, last) in sorted {
+        // Networks either nest or are disjoint, and an enclosing network
+        // sorts before the ones inside it.
+        if covered_until.is_some_and(|until| first.is_ipv4() == until.is_ipv4() && first <= until) {
+            continue;
+        }
+        covered_until = Some(last);
+        match first {
+            IpAddr::V4(_) => v4 += 2f64.powi(32 - len as i32) / 2f64.powi(32),
+            IpAddr::V6(_) => v6 += 2f64.powi(128 - len as i32) / 2f64.powi(128),
+        }
+    }
+    (v4, v6)
+}
+
+/// Formats a share as a percentage, switching to scientific notation for the
+/// tiny fractions typical of IPv6.
+fn percent(share: f64) -> String {
+    let percent = share * 100.0;
+    if percent == 0.0 || percent >= 0.001 {
+        format!("{:.3}%", percent)
+    } else {
+        format!("{:.3e}%", percent)
+    }
+}
+
+fn print_counts<K: ToString>(
+    title: &str,
+    counts: impl IntoIterator<Item = (K, usize)>,
+    limit: usize,
+) {
+    let mut counts: Vec<_> = counts
+        .into_iter()
+        .map(|(key, count)| (key.to_string(), count))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    if counts.len() > limit {
+        println!("{} (top {} of {}):", title, limit, counts.len());
+    } else {
+        println!("{}:", title);
+    }
+    for (key, count) in counts.into_iter().take(limit) {
+        println!("  {:>8}  {}", count, key);
+    }
+}
+
+fn validate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (format, path) = match args {
+        [path] => (None, path),
+        [flag, format, path] if flag == "--format" => (
+            Some(
+                format
+                    .parse::<ListFormat>()
+                    .map_err(|_| format!("unknown format: {}", format))?,
+            ),
+            path,
+        ),
+        _ => return Err(USAGE.into()),
+    };
+
+    let report = match format {
+        Some(format) => IpDatabase::default().merge_list(
+            path,
+            format,
+            &SourceOptions::default(),
+            LoadMode::Lenient,
+        )?,
+        None => IpDatabase::load_from_file(path, LoadMode::Lenient)?.1,
+    };
+    print_report(path, &report);
+    for overlap in &report.overlaps {
+        println!(
+            "{}: warning: {} overlaps {}",
+            path, overlap.cidr, overlap.within
+        );
+    }
+
+    if report.skipped.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} invalid rows in {}", report.skipped.len(), path).into())
+    }
+}
+
+/// What `diff` compares for a network: its primary entry's classification.
+#[derive(PartialEq)]
+struct Classification {
+    category: Option<Category>,
+    provider: String,
+    asn: u32,
+}
+
+impl Classification {
+    fn of(entry: &IpMatch) -> Self {
+        Self {
+            category: entry.category,
+            provider: entry.provider.to_string(),
+            asn: entry.asn,
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} {} (AS{})",
+            category_name(self.category),
+            self.provider,
+            self.asn
+        )
+    }
+}
+
+fn diff(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let [old, new] = args else {
+        return Err(USAGE.into());
+    };
+    // Keyed by network; the primary entry comes last and wins.
+    let networks = |ip_db: IpDatabase| -> BTreeMap<(IpAddr, u8), (IpCidr, Classification)> {
+        ip_db
+            .entries()
+            .map(|entry| {
+                let key = (entry.cidr.first_address(), entry.cidr.network_length());
+                (key, (entry.cidr, Classification::of(&entry)))
+            })
+            .collect()
+    };
+    let old_networks = networks(open(old)?);
+    let mut new_networks = networks(open(new)?);
+
+    let (mut added, mut removed, mut reclassified) = (0, 0, 0);
+    for (key, (cidr, before)) in &old_networks {
+        match new_networks.remove(key) {
+            None => {
+                removed += 1;
+                println!("- {}  {}", cidr, before.describe());
+            }
+            Some((_, after)) if after != *before => {
+                reclassified += 1;
+                println!("~ {}  {} -> {}", cidr, before.describe(), after.describe());
+            }
+            Some(_) => {}
+        }
+    }
+    for (cidr, after) in new_networks.values() {
+        added += 1;
+        println!("+ {}  {}", cidr, after.describe());
+    }
+
+    println!(
+        "{} added, {} removed, {} reclassified",
+        added, removed, reclassified
+    );
+    Ok(())
+}
+
+fn print_report(path: &str, report: &LoadReport) {
+    println!(
+        "{}: {} entries, {} skipped, {} duplicates, {} overlapping ranges",
+        path,
+        report.loaded,
+        report.skipped.len(),
+        report.duplicates,
+        report.overlaps.len()
+    );
+    for skipped in &report.skipped {
+        eprintln!("{}:{}: skipped: {}", path, skipped.row, skipped.reason);
+    }
+}
+
+fn compile(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut mode = LoadMode::Strict;
+    let mut format: Option<ListFormat> = None;
+    let mut options = SourceOptions::default();
+    let mut output = None;
+    let mut ip_db = IpDatabase::default();
+    let mut feeds = 0;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lenient" => mode = LoadMode::Lenient,
+            "--format" => {
+                let value = args.next().ok_or(USAGE)?;
+                format = match value.as_str() {
+                    "csv" => None,
+                    other => Some(
+                        other
+                            .parse()
+                            .map_err(|_| format!("unknown format: {}", other))?,
+                    ),
+                };
+            }
+            "--category" => {
+                let value = args.next().ok_or(USAGE)?;
+                options.category = value
+                    .parse()
+                    .map_err(|_| format!("unknown category: {}", value))?;
+            }
+            "--weight" => {
+                let value = args.next().ok_or(USAGE)?;
+                options.weight = value
+                    .parse()
+                    .ok()
+                    .filter(|weight| (0.0..=1.0).contains(weight))
+                    .ok_or_else(|| format!("invalid weight, expected 0.0 to 1.0: {}", value))?;
+            }
+            path if output.is_none() => output = Some(path),
+            feed => {
+                let report = match format {
+                    Some(format) => ip_db.merge_list(feed, format, &options, mode)?,
+                    None => ip_db.merge_csv(feed, &options, mode)?,
+                };
+                feeds += 1;
+                print_report(feed, &report);
+            }
+        }
+    }
+
+    let output = output.filter(|_| feeds > 0).ok_or(USAGE)?;
+    ip_db.write_snapshot(output)?;
+    println!("Wrote snapshot {}", output);
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use crate::trie::network;
+use crate::{AddressForm, Category, Coordinates, GeoIpError, IpMatch, Location, SourceMatch};
+use cidr_utils::cidr::IpCidr;
+use maxminddb::{geoip2, Reader};
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Locations of local MaxMind databases. Any subset may be configured.
+#[derive(Debug, Clone, Default)]
+pub struct MmdbPaths {
+    /// GeoIP2-Anonymous-IP: VPN, hosting, Tor exit and proxy flags.
+    pub anonymous_ip: Option<PathBuf>,
+    /// GeoLite2-ASN: autonomous system number and organisation.
+    pub asn: Option<PathBuf>,
+    /// GeoLite2-City (or Country): registered location.
+    pub city: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct MmdbFile {
+    reader: Reader<Vec<u8>>,
+    source: Arc<str>,
+}
+
+impl MmdbFile {
+    fn open(path: &Path) -> Result<Self, GeoIpError> {
+        Ok(Self {
+            reader: Reader::open_readfile(path)?,
+            source: path.display().to_string().into(),
+        })
+    }
+
+    /// Returns the record for `ip` and the network it was stored under.
+    ///
+    /// Decoding errors are treated like a miss: the file was validated when
+    /// opened, and one bad record should not fail the whole check.
+    fn find<'de, T: Deserialize<'de>>(&'de self, ip: IpAddr) -> Option<(T, IpCidr)> {
+        let (record, prefix_len) = self.reader.lookup_prefix::<T>(ip).ok()?;
+        Some((record, network(ip, prefix_len as u8)?))
+    }
+}
+
+/// Lookup backend over GeoIP2/GeoLite2 `.mmdb` files.
+#[derive(Debug)]
+pub(crate) struct MmdbDatabase {
+    anonymous_ip: Option<MmdbFile>,
+    asn: Option<MmdbFile>,
+    city: Option<MmdbFile>,
+}
+
+impl MmdbDatabase {
+    pub(crate) fn open(paths: &MmdbPaths) -> Result<Self, GeoIpError> {
+        let open = |path: &Option<PathBuf>| path.as_deref().map(MmdbFile::open).transpose();
+        let database = Self {
+            anonymous_ip: open(&paths.anonymous_ip)?,
+            asn: open(&paths.asn)?,
+            city: open(&paths.city)?,
+        };
+
+        if database.anonymous_ip.is_none() && database.asn.is_none() && database.city.is_none() {
+            return Err(GeoIpError::NoMmdbConfigured);
+        }
+        Ok(database)
+    }
+
+    pub(crate) fn lookup(&self, ip: IpAddr) -> Option<IpMatch> {
+        let anonymous = self
+            .anonymous_ip
+            .as_ref()
+            .and_then(|db| Some((db.find::<geoip2::AnonymousIp>(ip)?, &db.source)));
+        let asn = self
+            .asn
+            .as_ref()
+            .and_then(|db| Some((db.find::<geoip2::Asn>(ip)?, &db.source)));
+        let city = self
+            .city
+            .as_ref()
+            .and_then(|db| Some((db.find::<geoip2::City>(ip)?, &db.source)));
+
+        // The anonymous-IP range is what flags the address, so it is the one
+        // reported when present.
+        let (cidr, source) = anonymous
+            .as_ref()
+            .map(|((_, cidr), source)| (*cidr, *source))
+            .or_else(|| asn.as_ref().map(|((_, cidr), source)| (*cidr, *source)))
+            .or_else(|| city.as_ref().map(|((_, cidr), source)| (*cidr, *source)))?;
+
+        let category = anonymous
+            .as_ref()
+            .and_then(|((record, _), _)| category(record));
+
+        let (asn_number, provider) = asn
+            .as_ref()
+            .map(|((record, _), _)| {
+                (
+                    record.autonomous_system_number.unwrap_or(0),
+                    record.autonomous_system_organization.unwrap_or(""),
+                )
+            })
+            .unwrap_or((0, ""));
+
+        let location = city
+            .as_ref()
+            .map(|((record, _), _)| location(record))
+            .unwrap_or_default();
+
+        Some(IpMatch {
+            cidr,
+            address: ip,
+            form: AddressForm::Direct,
+            asn: asn_number,
+            provider: provider.into(),
+            source: Arc::clone(source),
+            country: location.country,
+            region: location.region,
+            coordinates: location.coordinates,
+            category,
+            confidence: 1.0,
+            first_seen: None,
+            last_seen: None,
+            published: None,
+            sources: vec![SourceMatch {
+                source: Arc::clone(source),
+                cidr,
+                category,
+                weight: 1.0,
+                confidence: 1.0,
+                last_seen: None,
+            }],
+        })
+    }
+}
+
+/// A City database opened only to locate addresses.
+#[derive(Debug)]
+pub(crate) struct GeoDatabase {
+    city: MmdbFile,
+}
+
+impl GeoDatabase {
+    pub(crate) fn open(path: &Path) -> Result<Self, GeoIpError> {
+        Ok(Self {
+            city: MmdbFile::open(path)?,
+        })
+    }
+
+    pub(crate) fn locate(&self, ip: IpAddr) -> Option<Location> {
+        let (record, _) = self.city.find::<geoip2::City>(ip)?;
+        Some(location(&record)).filter(|location| !location.is_empty())
+    }
+}
+
+/// Country, first-level subdivision and coordinates of a City record.
+/// Country databases carry only the country.
+fn location(record: &geoip2::City) -> Location {
+    let coordinates = record
+        .location
+        .as_ref()
+        .and_then(|location| Coordinates::new(location.latitude?, location.longitude?));
+    Location {
+        country: record
+            .country
+            .as_ref()
+            .and_then(|country| country.iso_code)
+            .map(Arc::from),
+        region: record
+            .subdivisions
+            .as_ref()
+            .and_then(|subdivisions| subdivisions.first()?.iso_code)
+            .map(Arc::from),
+        coordinates,
+    }
+}
+
+/// Collapses the anonymous-IP flags into one category, most specific first.
+fn category(record: &geoip2::AnonymousIp) -> Option<Category> {
+    let flagged = |flag: Option<bool>| flag.unwrap_or(false);
+    [
+        (record.is_tor_exit_node, Category::Tor),
+        (record.is_anonymous_vpn, Category::Vpn),
+        (record.is_public_proxy, Category::Proxy),
+        (record.is_residential_proxy, Category::ResidentialProxy),
+        (record.is_hosting_provider, Category::Hosting),
+    ]
+    .into_iter()
+    .find(|(flag, _)| flagged(*flag))
+    .map(|(_, category)| category)
+}
@@ -0,0 +1,123 @@
+use crate::{GeoIpError, IpTrie};
+use cidr_utils::cidr::IpCidr;
+use serde::Deserialize;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Operator decision that replaces every other signal for a range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Verdict {
+    /// Never report the range as an anonymizer.
+    Allow,
+    /// Always report the range as an anonymizer.
+    Flag,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Allow => "allow",
+            Verdict::Flag => "flag",
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Verdict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "allow" => Ok(Verdict::Allow),
+            "flag" => Ok(Verdict::Flag),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+/// An override entry that applies to a looked-up address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Override {
+    pub cidr: IpCidr,
+    pub verdict: Verdict,
+    pub reason: Option<Arc<str>>,
+    pub expires: Option<SystemTime>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OverrideRecord {
+    cidr: String,
+    verdict: String,
+    #[serde(default)]
+    reason: Option<String>,
+    /// RFC 3339 timestamp, e.g. `2025-01-31T00:00:00Z`.
+    #[serde(default)]
+    expires: Option<String>,
+}
+
+/// Operator allowlist/denylist loaded from a `cidr,verdict,reason,expires` CSV.
+#[derive(Debug, Default)]
+pub(crate) struct OverrideList {
+    index: IpTrie<Override>,
+}
+
+impl OverrideList {
+    pub(crate) fn load_csv(path: &Path) -> Result<Self, GeoIpError> {
+        let mut rdr = csv::Reader::from_path(path)?;
+        let mut list = Self::default();
+
+        for result in rdr.deserialize() {
+            let record: OverrideRecord = result?;
+            let cidr = record
+                .cidr
+                .parse::<IpCidr>()
+                .map_err(|_| GeoIpError::InvalidCidr(record.cidr.clone()))?;
+            let verdict = record.verdict.parse().map_err(GeoIpError::InvalidVerdict)?;
+            let expires = match record.expires.as_deref().map(str::trim) {
+                None | Some("") => None,
+                Some(expires) => Some(
+                    humantime::parse_rfc3339_weak(expires)
+                        .map_err(|_| GeoIpError::InvalidTimestamp(expires.to_string()))?,
+                ),
+            };
+
+            list.index.insert(
+                &cidr,
+                Override {
+                    cidr,
+                    verdict,
+                    reason: record
+                        .reason
+                        .filter(|reason| !reason.trim().is_empty())
+                        .map(Arc::from),
+                    expires,
+                },
+            );
+        }
+
+        Ok(list)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Most specific override for `ip` that has not expired at `now`.
+    pub(crate) fn check(&self, ip: IpAddr, now: SystemTime) -> Option<&Override> {
+        self.index
+            .matches(ip)
+            .into_iter()
+            .rev()
+            .map(|(entry, _)| entry)
+            .find(|entry| entry.expires.is_none_or(|expires| expires > now))
+    }
+}
@@ -0,0 +1,45 @@
+use cidr_utils::cidr::IpCidr;
+
+/// How a feed loader reacts to rows it cannot parse.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Abort on the first invalid row. Use this to validate feeds in CI.
+    #[default]
+    Strict,
+    /// Skip invalid rows and record them in the [`LoadReport`].
+    Lenient,
+}
+
+/// A row that was left out of the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRow {
+    /// 1-based line in the file, counting the header.
+    pub row: u64,
+    pub reason: String,
+}
+
+/// A range from this load nested inside another range in the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    pub cidr: IpCidr,
+    /// Closest enclosing network; lookups inside `cidr` ignore it.
+    pub within: IpCidr,
+}
+
+/// Summary of a feed load.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// Networks added to the database, duplicates included. A range row
+    /// may add several.
+    pub loaded: usize,
+    pub skipped: Vec<SkippedRow>,
+    /// Rows whose network was already listed; the later row wins.
+    pub duplicates: usize,
+    pub overlaps: Vec<Overlap>,
+}
+
+impl LoadReport {
+    pub fn is_clean(&self) -> bool {
+        self.skipped.is_empty() && self.duplicates == 0 && self.overlaps.is_empty()
+    }
+}
@@ -0,0 +1,396 @@
+//! Precompiled binary form of a [`RangeTable`].
+//!
+//! Layout, all integers little-endian:
+//!
+//! ```text
+//! header   magic "GEOIPSNP" | version u32 | crc32(payload) u32 | payload length u64
+//! payload  created u64 (unix seconds)
+//!          strings:  count u32, then (length u32, UTF-8 bytes) each
+//!          entries:  count u32, then per entry
+//!                    address u128 (IPv4 as u32 widened) | family u8 (4 or 6)
+//!                    prefix u8 | asn u32 | category u8 | provider u32 | source u32
+//!                    weight f32 | confidence f32
+//!                    first_seen u64 | last_seen u64 | published u64 |
+//!                    expires u64
+//!                    (timestamps in unix seconds, u64::MAX when unknown;
+//!                    category u8::MAX when not flagged)
+//!                    country u32 | region u32 (u32::MAX when unknown)
+//!                    located u8 (0 or 1), then latitude f64 | longitude f64
+//!                    when 1
+//!          trie:     IPv4 arena then IPv6 arena, each
+//!                    count u32, then per node child0 u32 | child1 u32 |
+//!                    entry count u32 | entry u32 each
+//! ```
+
+use crate::table::{IpEntry, RangeTable};
+use crate::trie::RawNode;
+use crate::{Category, Coordinates, GeoIpError, IpTrie};
+use cidr_utils::cidr::IpCidr;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) const MAGIC: &[u8; 8] = b"GEOIPSNP";
+pub(crate) const VERSION: u32 = 5;
+/// String index standing in for a missing optional string.
+const NO_STRING: u32 = u32::MAX;
+/// Category code of ranges listed without being flagged.
+const UNFLAGGED: u8 = u8::MAX;
+const HEADER_LEN: usize = 24;
+
+/// Metadata stored alongside a compiled snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotMetadata {
+    pub version: u32,
+    /// When the snapshot was compiled.
+    pub created: SystemTime,
+    /// Feeds the snapshot was compiled from, in load order.
+    pub sources: Vec<Arc<str>>,
+}
+
+pub(crate) fn is_snapshot(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub(crate) fn encode(table: &RangeTable) -> Vec<u8> {
+    let mut strings = StringTable::default();
+    let mut payload = Vec::new();
+    put_u64(&mut payload, unix_secs(SystemTime::now()));
+
+    // Entries replaced by a later duplicate are left out. The rest keep
+    // their order, so the index only needs renumbering.
+    let raw_nodes = table.index().raw_nodes();
+    let mut renumbered = vec![None; table.entries().len()];
+    for (_, value) in raw_nodes.iter().flatten() {
+        for &index in value.map_or(&[][..], Vec::as_slice) {
+            renumbered[index] = Some(0);
+        }
+    }
+    for (kept, slot) in renumbered.iter_mut().flatten().enumerate() {
+        *slot = kept as u32;
+    }
+
+    let entries: Vec<_> = table
+        .entries()
+        .iter()
+        .zip(&renumbered)
+        .filter(|(_, renumbered)| renumbered.is_some())
+        .map(|(entry, _)| {
+            (
+                entry,
+                strings.intern(&entry.provider),
+                strings.intern(&entry.source),
+                entry
+                    .country
+                    .as_ref()
+                    .map_or(NO_STRING, |c| strings.intern(c)),
+                entry
+                    .region
+                    .as_ref()
+                    .map_or(NO_STRING, |r| strings.intern(r)),
+            )
+        })
+        .collect();
+
+    put_u32(&mut payload, strings.values.len() as u32);
+    for value in &strings.values {
+        put_u32(&mut payload, value.len() as u32);
+        payload.extend_from_slice(value.as_bytes());
+    }
+
+    put_u32(&mut payload, entries.len() as u32);
+    for (entry, provider, source, country, region) in entries {
+        let (address, family) = match entry.cidr.first_address() {
+            IpAddr::V4(addr) => (u32::from(addr) as u128, 4),
+            IpAddr::V6(addr) => (u128::from(addr), 6),
+        };
+        payload.extend_from_slice(&address.to_le_bytes());
+        payload.push(family);
+        payload.push(entry.cidr.network_length());
+        put_u32(&mut payload, entry.asn);
+        payload.push(category_code(entry.category));
+        put_u32(&mut payload, provider);
+        put_u32(&mut payload, source);
+        put_u32(&mut payload, entry.weight.to_bits());
+        put_u32(&mut payload, entry.confidence.to_bits());
+        for timestamp in [
+            entry.first_seen,
+            entry.last_seen,
+            entry.published,
+            entry.expires,
+        ] {
+            put_u64(&mut payload, timestamp.map_or(u64::MAX, unix_secs));
+        }
+        put_u32(&mut payload, country);
+        put_u32(&mut payload, region);
+        match entry.coordinates {
+            Some(coordinates) => {
+                payload.push(1);
+                put_u64(&mut payload, coordinates.latitude.to_bits());
+                put_u64(&mut payload, coordinates.longitude.to_bits());
+            }
+            None => payload.push(0),
+        }
+    }
+
+    for nodes in raw_nodes {
+        put_u32(&mut payload, nodes.len() as u32);
+        for (children, value) in nodes {
+            put_u32(&mut payload, children[0]);
+            put_u32(&mut payload, children[1]);
+            let indices = value.map_or(&[][..], Vec::as_slice);
+            put_u32(&mut payload, indices.len() as u32);
+            for &index in indices {
+                put_u32(&mut payload, renumbered[index].expect("indexed entry"));
+            }
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    put_u32(&mut bytes, VERSION);
+    put_u32(&mut bytes, crc32fast::hash(&payload));
+    put_u64(&mut bytes, payload.len() as u64);
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<(RangeTable, SnapshotMetadata), GeoIpError> {
+    let mut header = Reader::new(bytes);
+    if header.bytes(MAGIC.len())? != MAGIC {
+        return Err(invalid("missing snapshot magic"));
+    }
+    let version = header.u32()?;
+    if version != VERSION {
+        return Err(GeoIpError::UnsupportedSnapshotVersion(version));
+    }
+    let checksum = header.u32()?;
+    let payload_len = header.u64()?;
+    let payload = header.rest();
+    if payload.len() as u64 != payload_len {
+        return Err(invalid("payload length mismatch"));
+    }
+    if crc32fast::hash(payload) != checksum {
+        return Err(invalid("checksum mismatch"));
+    }
+
+    let mut rdr = Reader::new(payload);
+    let created = unix_time(rdr.u64()?)?;
+
+    let string_count = rdr.u32()?;
+    let mut strings = Vec::new();
+    for _ in 0..string_count {
+        let len = rdr.u32()? as usize;
+        let value =
+            std::str::from_utf8(rdr.bytes(len)?).map_err(|_| invalid("string is not UTF-8"))?;
+        strings.push(Arc::<str>::from(value));
+    }
+    let string = |index: u32| {
+        strings
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| invalid("string index out of range"))
+    };
+    let optional_string = |index: u32| match index {
+        NO_STRING => Ok(None),
+        index => string(index).map(Some),
+    };
+
+    let entry_count = rdr.u32()?;
+    let mut entries = Vec::new();
+    let mut sources: Vec<Arc<str>> = Vec::new();
+    for _ in 0..entry_count {
+        let address = u128::from_le_bytes(rdr.bytes(16)?.try_into().expect("16 bytes"));
+        let address = match rdr.u8()? {
+            4 => IpAddr::V4(Ipv4Addr::from(
+                u32::try_from(address).map_err(|_| invalid("IPv4 address out of range"))?,
+            )),
+            6 => IpAddr::V6(Ipv6Addr::from(address)),
+            _ => return Err(invalid("unknown address family")),
+        };
+        let cidr = IpCidr::new(address, rdr.u8()?).map_err(|_| invalid("invalid network"))?;
+        let asn = rdr.u32()?;
+        let category = match rdr.u8()? {
+            UNFLAGGED => None,
+            code => Some(
+                *Category::ALL
+                    .get(code as usize)
+                    .ok_or_else(|| invalid("unknown category"))?,
+            ),
+        };
+        let provider = string(rdr.u32()?)?;
+        let source = string(rdr.u32()?)?;
+        let weight = f32::from_bits(rdr.u32()?);
+        let confidence = f32::from_bits(rdr.u32()?);
+        let first_seen = rdr.timestamp()?;
+        let last_seen = rdr.timestamp()?;
+        let published = rdr.timestamp()?;
+        let expires = rdr.timestamp()?;
+        let country = optional_string(rdr.u32()?)?;
+        let region = optional_string(rdr.u32()?)?;
+        let coordinates = match rdr.u8()? {
+            0 => None,
+            1 => {
+                let latitude = f64::from_bits(rdr.u64()?);
+                let longitude = f64::from_bits(rdr.u64()?);
+                Some(
+                    Coordinates::new(latitude, longitude)
+                        .ok_or_else(|| invalid("coordinates out of range"))?,
+                )
+            }
+            _ => return Err(invalid("invalid location flag")),
+        };
+
+        if !sources.contains(&source) {
+            sources.push(Arc::clone(&source));
+        }
+        entries.push(IpEntry {
+            cidr,
+            asn,
+            provider,
+            category,
+            source,
+            weight,
+            confidence,
+            first_seen,
+            last_seen,
+            published,
+            expires,
+            country,
+            region,
+            coordinates,
+        });
+    }
+
+    let mut arenas = Vec::with_capacity(2);
+    for _ in 0..2 {
+        let node_count = rdr.u32()?;
+        let mut nodes: Vec<RawNode<Vec<usize>>> = Vec::new();
+        for _ in 0..node_count {
+            let children = [rdr.u32()?, rdr.u32()?];
+            let mut indices = Vec::new();
+            for _ in 0..rdr.u32()? {
+                match rdr.u32()? {
+                    index if index < entry_count => indices.push(index as usize),
+                    _ => return Err(invalid("entry index out of range")),
+                }
+            }
+            let value = (!indices.is_empty()).then_some(indices);
+            nodes.push((children, value));
+        }
+        arenas.push(nodes);
+    }
+    if !rdr.rest().is_empty() {
+        return Err(invalid("trailing bytes after trie"));
+    }
+    let v6 = arenas.pop().expect("two arenas");
+    let v4 = arenas.pop().expect("two arenas");
+    // Each entry has to sit at the node of its own network, or lookups would
+    // return ranges that do not contain the address.
+    let index = IpTrie::from_raw_nodes(v4, v6, |cidr, indices: &Vec<usize>| {
+        indices.iter().all(|&index| entries[index].cidr == *cidr)
+    })
+    .ok_or_else(|| invalid("malformed trie"))?;
+
+    let metadata = SnapshotMetadata {
+        version,
+        created,
+        sources,
+    };
+    Ok((RangeTable::from_parts(entries, index), metadata))
+}
+
+fn invalid(reason: &str) -> GeoIpError {
+    GeoIpError::InvalidSnapshot(reason.to_string())
+}
+
+fn category_code(category: Option<Category>) -> u8 {
+    category.map_or(UNFLAGGED, |category| {
+        Category::ALL
+            .iter()
+            .position(|&c| c == category)
+            .expect("category listed in Category::ALL") as u8
+    })
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn unix_time(secs: u64) -> Result<SystemTime, GeoIpError> {
+    UNIX_EPOCH
+        .checked_add(Duration::from_secs(secs))
+        .ok_or_else(|| invalid("timestamp out of range"))
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+#[derive(Default)]
+struct StringTable {
+    values: Vec<Arc<str>>,
+    positions: HashMap<Arc<str>, u32>,
+}
+
+impl StringTable {
+    fn intern(&mut self, value: &Arc<str>) -> u32 {
+        *self.positions.entry(Arc::clone(value)).or_insert_with(|| {
+            self.values.push(Arc::clone(value));
+            (self.values.len() - 1) as u32
+        })
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], GeoIpError> {
+        if self.buf.len() < len {
+            return Err(invalid("unexpected end of snapshot"));
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, GeoIpError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, GeoIpError> {
+        Ok(u32::from_le_bytes(
+            self.bytes(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64, GeoIpError> {
+        Ok(u64::from_le_bytes(
+            self.bytes(8)?.try_into().expect("8 bytes"),
+        ))
+    }
+
+    fn timestamp(&mut self) -> Result<Option<SystemTime>, GeoIpError> {
+        Ok(match self.u64()? {
+            u64::MAX => None,
+            secs => Some(unix_time(secs)?),
+        })
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.buf)
+    }
+}
@@ -0,0 +1,37 @@
+use crate::Category;
+use cidr_utils::cidr::IpCidr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Defaults applied to every entry merged from one feed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceOptions {
+    /// Category for entries that do not name one; plain lists never do.
+    pub category: Category,
+    /// How far the feed is trusted, from `0.0` to `1.0`.
+    pub weight: f32,
+}
+
+impl Default for SourceOptions {
+    fn default() -> Self {
+        Self {
+            category: Category::Vpn,
+            weight: 1.0,
+        }
+    }
+}
+
+/// One source's most specific entry for a looked-up address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceMatch {
+    pub source: Arc<str>,
+    pub cidr: IpCidr,
+    /// `None` when the source knows the range but does not flag it.
+    pub category: Option<Category>,
+    /// Trust in the source as a whole, from its configuration.
+    pub weight: f32,
+    /// Trust in this entry, from the feed's `confidence` column.
+    pub confidence: f32,
+    /// When the source last saw the range, if it says.
+    pub last_seen: Option<SystemTime>,
+}
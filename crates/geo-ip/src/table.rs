@@ -0,0 +1,460 @@
+use crate::asn;
+use crate::cache::{CacheStats, ShardedLru};
+use crate::compression;
+use crate::import::{self, ListFormat};
+use crate::{
+    AddressForm, Category, Coordinates, GeoIpError, IpMatch, IpTrie, LoadMode, LoadReport, Overlap,
+    SkippedRow, SourceMatch, SourceOptions,
+};
+use cidr_utils::cidr::IpCidr;
+use serde::Deserialize;
+use std::io::Read;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Addresses the lookup cache holds unless configured otherwise.
+const DEFAULT_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(1000).unwrap();
+
+/// CSV category of ranges that are listed but not flagged, e.g. an ISP
+/// carved out of a wider VPN range.
+const UNFLAGGED: &str = "none";
+
+#[derive(Debug, Deserialize)]
+struct CsvRecord {
+    cidr: String,
+    asn: String,
+    provider: String,
+    /// Optional; rows without it get the source's default category, except
+    /// that `asn` 0 keeps its old meaning of "listed but not flagged". `none`
+    /// says so explicitly.
+    #[serde(default)]
+    category: Option<String>,
+    /// Optional `0.0`-`1.0`; rows without it are fully trusted.
+    #[serde(default)]
+    confidence: Option<String>,
+    /// Optional RFC 3339 timestamps of when the feed first and last saw the
+    /// range.
+    #[serde(default)]
+    first_seen: Option<String>,
+    #[serde(default)]
+    last_seen: Option<String>,
+    /// Optional location: ISO 3166-1 alpha-2 country, region, and decimal
+    /// latitude and longitude (both or neither).
+    #[serde(default)]
+    country: Option<String>,
+    #[serde(default)]
+    region: Option<String>,
+    #[serde(default)]
+    latitude: Option<String>,
+    #[serde(default)]
+    longitude: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct IpEntry {
+    pub(crate) cidr: IpCidr,
+    pub(crate) asn: u32,
+    pub(crate) provider: Arc<str>,
+    /// `None` for ranges the source lists without flagging them.
+    pub(crate) category: Option<Category>,
+    pub(crate) source: Arc<str>,
+    pub(crate) weight: f32,
+    pub(crate) confidence: f32,
+    pub(crate) first_seen: Option<SystemTime>,
+    pub(crate) last_seen: Option<SystemTime>,
+    pub(crate) published: Option<SystemTime>,
+    /// After this time the entry no longer matches, e.g. a Tor exit whose
+    /// descriptor is too old.
+    pub(crate) expires: Option<SystemTime>,
+    pub(crate) country: Option<Arc<str>>,
+    pub(crate) region: Option<Arc<str>>,
+    pub(crate) coordinates: Option<Coordinates>,
+}
+
+impl IpEntry {
+    /// This entry alone as a match, listing only its own source.
+    pub(crate) fn to_match(&self) -> IpMatch {
+        IpMatch {
+            cidr: self.cidr,
+            address: self.cidr.first_address(),
+            form: AddressForm::Direct,
+            asn: self.asn,
+            provider: Arc::clone(&self.provider),
+            source: Arc::clone(&self.source),
+            country: self.country.clone(),
+            region: self.region.clone(),
+            coordinates: self.coordinates,
+            category: self.category,
+            confidence: self.confidence,
+            first_seen: self.first_seen,
+            last_seen: self.last_seen,
+            published: self.published,
+            sources: vec![self.source_match()],
+        }
+    }
+
+    pub(crate) fn source_match(&self) -> SourceMatch {
+        SourceMatch {
+            source: Arc::clone(&self.source),
+            cidr: self.cidr,
+            category: self.category,
+            weight: self.weight,
+            confidence: self.confidence,
+            last_seen: self.last_seen.or(self.published),
+        }
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+/// In-memory range table built from `cidr,asn,provider[,category]` feeds.
+///
+/// Each network keeps at most one entry per source, so feeds that list the
+/// same range all show up in a lookup.
+#[derive(Debug)]
+pub(crate) struct RangeTable {
+    entries: Vec<IpEntry>,
+    index: IpTrie<Vec<usize>>,
+    /// Indices of every entry containing an address, most specific network
+    /// first and the last added first within a network; empty for addresses
+    /// no entry contains. Expiry is applied on lookup, so entries stay
+    /// cached while they age.
+    match_cache: ShardedLru<IpAddr, Arc<[usize]>>,
+}
+
+impl RangeTable {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            index: IpTrie::new(),
+            match_cache: ShardedLru::new(DEFAULT_CACHE_CAPACITY),
+        }
+    }
+
+    /// Replaces the lookup cache with an empty one holding `capacity`
+    /// addresses, resetting its counters.
+    pub(crate) fn set_cache_capacity(&mut self, capacity: NonZeroUsize) {
+        self.match_cache = ShardedLru::new(capacity);
+    }
+
+    pub(crate) fn cache_stats(&self) -> CacheStats {
+        self.match_cache.stats()
+    }
+
+    /// Rebuilds a table from its entries and an index into them.
+    pub(crate) fn from_parts(entries: Vec<IpEntry>, index: IpTrie<Vec<usize>>) -> Self {
+        Self {
+            entries,
+            index,
+            ..Self::new()
+        }
+    }
+
+    /// All entries ever inserted, including ones replaced by a duplicate.
+    pub(crate) fn entries(&self) -> &[IpEntry] {
+        &self.entries
+    }
+
+    /// Entries currently indexed, in address order; a network listed by
+    /// several sources yields each, its primary entry last.
+    pub(crate) fn indexed_entries(&self) -> impl Iterator<Item = &IpEntry> + '_ {
+        self.index
+            .iter()
+            .flat_map(|(_, indices)| indices.iter().map(|&index| &self.entries[index]))
+    }
+
+    pub(crate) fn index(&self) -> &IpTrie<Vec<usize>> {
+        &self.index
+    }
+
+    /// Adds the rows of a `cidr,asn,provider[,category]` CSV feed read from
+    /// `reader`, tagging them with `source`.
+    pub(crate) fn merge_csv<R: Read>(
+        &mut self,
+        reader: R,
+        source: Arc<str>,
+        options: &SourceOptions,
+        mode: LoadMode,
+    ) -> Result<LoadReport, GeoIpError> {
+        self.clear_cache();
+        let mut rdr = csv::Reader::from_reader(reader);
+        let headers = rdr.headers()?.clone();
+        let first = self.entries.len();
+        let mut report = LoadReport::default();
+
+        for result in rdr.records() {
+            let row = match &result {
+                Ok(record) => record.position(),
+                Err(e) if e.is_io_error() => return Err(result.unwrap_err().into()),
+                Err(e) => e.position(),
+            }
+            .map_or(0, |position| position.line());
+
+            let entry = result.map_err(GeoIpError::from).and_then(|record| {
+                let record: CsvRecord = record.deserialize(Some(&headers))?;
+                parse_record(record, &source, options)
+            });
+            match entry {
+                Ok(entry) => {
+                    if self.insert(entry) {
+                        report.duplicates += 1;
+                    }
+                    report.loaded += 1;
+                }
+                Err(e) if mode == LoadMode::Lenient => report.skipped.push(SkippedRow {
+                    row,
+                    reason: e.to_string(),
+                }),
+                Err(e) => return Err(e),
+            }
+        }
+
+        report.overlaps = self.overlaps(first);
+        Ok(report)
+    }
+
+    /// Adds the networks of a plain address list. The file stem becomes the
+    /// provider name; lists carry no ASN.
+    pub(crate) fn merge_list(
+        &mut self,
+        path: &Path,
+        format: ListFormat,
+        options: &SourceOptions,
+        mode: LoadMode,
+    ) -> Result<LoadReport, GeoIpError> {
+        let source: Arc<str> = path.display().to_string().into();
+        let provider: Arc<str> = compression::file_stem(path)
+            .map_or_else(
+                || source.to_string(),
+                |stem| stem.to_string_lossy().into_owned(),
+            )
+            .into();
+        self.clear_cache();
+        let first = self.entries.len();
+        let mut report = LoadReport::default();
+
+        for item in import::parse(compression::open(path)?, format)? {
+            match item.cidrs {
+                Ok(cidrs) => {
+                    for cidr in cidrs {
+                        if self.insert(IpEntry {
+                            cidr,
+                            asn: 0,
+                            provider: Arc::clone(&provider),
+                            category: Some(options.category),
+                            source: Arc::clone(&source),
+                            weight: options.weight,
+                            confidence: 1.0,
+                            first_seen: None,
+                            last_seen: None,
+                            published: None,
+                            expires: None,
+                            country: None,
+                            region: None,
+                            coordinates: None,
+                        }) {
+                            report.duplicates += 1;
+                        }
+                        report.loaded += 1;
+                    }
+                }
+                Err(reason) if mode == LoadMode::Lenient => report.skipped.push(SkippedRow {
+                    row: item.row,
+                    reason,
+                }),
+                Err(reason) => {
+                    return Err(GeoIpError::InvalidListEntry {
+                        line: item.row,
+                        reason,
+                    })
+                }
+            }
+        }
+
+        report.overlaps = self.overlaps(first);
+        Ok(report)
+    }
+
+    /// Empties the lookup cache ahead of a batch of inserts. Nothing can
+    /// look up while the table is borrowed mutably, so clearing before the
+    /// batch also covers one that stops early on an error.
+    pub(crate) fn clear_cache(&mut self) {
+        self.match_cache.clear();
+    }
+
+    /// Adds `entry`; a later entry from the same source for the same network
+    /// overrides an earlier one, in which case this returns `true`. Leaves
+    /// the lookup cache alone; see [`RangeTable::clear_cache`].
+    pub(crate) fn insert(&mut self, entry: IpEntry) -> bool {
+        let index = self.entries.len();
+        let indices = self.index.get_or_insert_with(&entry.cidr, Vec::new);
+        let before = indices.len();
+        indices.retain(|&i| self.entries[i].source != entry.source);
+        let replaced = indices.len() != before;
+        // The last entry of a network is its primary one.
+        indices.push(index);
+        self.entries.push(entry);
+        replaced
+    }
+
+    /// Indexed networks added from `first` on that sit inside another
+    /// indexed network.
+    fn overlaps(&self, first: usize) -> Vec<Overlap> {
+        let mut overlaps = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate().skip(first) {
+            let matches = self.index.matches(entry.cidr.first_address());
+            // Entries replaced by a later duplicate are no longer indexed.
+            let indexed = matches.last().is_some_and(|(indices, len)| {
+                *len == entry.cidr.network_length() && indices.last() == Some(&index)
+            });
+            if !indexed {
+                continue;
+            }
+            if let Some((outer, _)) = matches.iter().rev().nth(1) {
+                overlaps.push(Overlap {
+                    cidr: entry.cidr,
+                    within: self.entries[outer[0]].cidr,
+                });
+            }
+        }
+        overlaps
+    }
+
+    /// Entries containing `ip`, most specific first.
+    fn matched_entries(&self, ip: IpAddr) -> Vec<usize> {
+        self.index
+            .matches(ip)
+            .into_iter()
+            .rev()
+            .flat_map(|(indices, _)| indices.iter().rev().copied())
+            .collect()
+    }
+
+    /// Unexpired entries containing `ip`, one per source: the primary (most
+    /// specific, last added) entry first, then other sources by specificity.
+    pub(crate) fn lookup(&self, ip: IpAddr, now: SystemTime) -> Option<IpMatch> {
+        let cached = match self.match_cache.get(&ip) {
+            Some(matched) => matched,
+            None => {
+                let matched: Arc<[usize]> = self.matched_entries(ip).into();
+                // Misses are cached too: most checked addresses are clean.
+                self.match_cache.put(ip, Arc::clone(&matched));
+                matched
+            }
+        };
+        let mut matched: Vec<usize> = Vec::new();
+        for &index in cached.iter() {
+            let entry = &self.entries[index];
+            if !entry.is_expired(now)
+                && !matched
+                    .iter()
+                    .any(|&m| self.entries[m].source == entry.source)
+            {
+                matched.push(index);
+            }
+        }
+        if matched.is_empty() {
+            return None;
+        }
+
+        let mut ip_match = self.entries[matched[0]].to_match();
+        ip_match.sources = matched
+            .iter()
+            .map(|&index| self.entries[index].source_match())
+            .collect();
+        Some(ip_match)
+    }
+}
+
+fn parse_record(
+    record: CsvRecord,
+    source: &Arc<str>,
+    options: &SourceOptions,
+) -> Result<IpEntry, GeoIpError> {
+    let cidr = record
+        .cidr
+        .parse::<IpCidr>()
+        .map_err(|_| GeoIpError::InvalidCidr(record.cidr.clone()))?;
+    let asn =
+        asn::parse_asn(&record.asn).ok_or_else(|| GeoIpError::InvalidAsn(record.asn.clone()))?;
+    let category = match record.category.as_deref().map(str::trim) {
+        None | Some("") if asn == 0 => None,
+        None | Some("") => Some(options.category),
+        Some(category) if category.eq_ignore_ascii_case(UNFLAGGED) => None,
+        Some(category) => Some(category.parse().map_err(GeoIpError::InvalidCategory)?),
+    };
+    let confidence = match record.confidence.as_deref().map(str::trim) {
+        None | Some("") => 1.0,
+        Some(confidence) => confidence
+            .parse::<f32>()
+            .ok()
+            .filter(|c| (0.0..=1.0).contains(c))
+            .ok_or_else(|| GeoIpError::InvalidConfidence(confidence.to_string()))?,
+    };
+
+    Ok(IpEntry {
+        cidr,
+        asn,
+        provider: record.provider.into(),
+        category,
+        source: Arc::clone(source),
+        weight: options.weight,
+        confidence,
+        first_seen: parse_timestamp(record.first_seen.as_deref())?,
+        last_seen: parse_timestamp(record.last_seen.as_deref())?,
+        published: None,
+        expires: None,
+        country: parse_country(record.country.as_deref())?,
+        region: non_empty(record.region.as_deref()).map(Arc::from),
+        coordinates: parse_coordinates(record.latitude.as_deref(), record.longitude.as_deref())?,
+    })
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|value| !value.is_empty())
+}
+
+fn parse_country(value: Option<&str>) -> Result<Option<Arc<str>>, GeoIpError> {
+    match non_empty(value) {
+        None => Ok(None),
+        Some(code) if code.len() == 2 && code.bytes().all(|b| b.is_ascii_alphabetic()) => {
+            Ok(Some(code.to_ascii_uppercase().into()))
+        }
+        Some(code) => Err(GeoIpError::InvalidCountry(code.to_string())),
+    }
+}
+
+fn parse_coordinates(
+    latitude: Option<&str>,
+    longitude: Option<&str>,
+) -> Result<Option<Coordinates>, GeoIpError> {
+    match (non_empty(latitude), non_empty(longitude)) {
+        (None, None) => Ok(None),
+        (Some(latitude), Some(longitude)) => latitude
+            .parse()
+            .ok()
+            .zip(longitude.parse().ok())
+            .and_then(|(latitude, longitude)| Coordinates::new(latitude, longitude))
+            .map(Some)
+            .ok_or_else(|| GeoIpError::InvalidCoordinates(format!("{},{}", latitude, longitude))),
+        (latitude, longitude) => Err(GeoIpError::InvalidCoordinates(format!(
+            "{},{}",
+            latitude.unwrap_or_default(),
+            longitude.unwrap_or_default()
+        ))),
+    }
+}
+
+fn parse_timestamp(value: Option<&str>) -> Result<Option<SystemTime>, GeoIpError> {
+    match value.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => humantime::parse_rfc3339_weak(value)
+            .map(Some)
+            .map_err(|_| GeoIpError::InvalidTimestamp(value.to_string())),
+    }
+}
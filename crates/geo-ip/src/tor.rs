@@ -0,0 +1,67 @@
+use crate::GeoIpError;
+use std::io::BufRead;
+use std::net::IpAddr;
+use std::time::SystemTime;
+
+/// One exit address from a Tor exit list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TorExit {
+    pub(crate) ip: IpAddr,
+    /// `Published` time of the relay descriptor; the bulk list carries none.
+    pub(crate) published: Option<SystemTime>,
+}
+
+/// Parses either the bulk exit list (one address per line) or the detailed
+/// `exit-addresses` format, detected from the first meaningful line.
+pub(crate) fn parse<R: BufRead>(reader: R) -> Result<Vec<TorExit>, GeoIpError> {
+    let mut exits = Vec::new();
+    let mut detailed = None;
+    let mut published = None;
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| GeoIpError::InvalidTorExitList {
+            line: number + 1,
+            reason: reason.to_string(),
+        };
+
+        let mut fields = line.split_whitespace();
+        let keyword = fields.next().unwrap_or_default();
+        if !*detailed.get_or_insert(keyword == "ExitNode") {
+            let ip = keyword
+                .parse()
+                .map_err(|_| invalid("expected an IP address"))?;
+            exits.push(TorExit {
+                ip,
+                published: None,
+            });
+            continue;
+        }
+
+        match keyword {
+            "ExitNode" => published = None,
+            "Published" => {
+                let timestamp = fields.collect::<Vec<_>>().join(" ");
+                published = Some(
+                    humantime::parse_rfc3339_weak(&timestamp)
+                        .map_err(|_| invalid("malformed Published timestamp"))?,
+                );
+            }
+            "ExitAddress" => {
+                let ip = fields
+                    .next()
+                    .and_then(|ip| ip.parse().ok())
+                    .ok_or_else(|| invalid("ExitAddress without a valid IP"))?;
+                exits.push(TorExit { ip, published });
+            }
+            // LastStatus and any future keywords carry nothing we use.
+            _ => {}
+        }
+    }
+
+    Ok(exits)
+}
@@ -0,0 +1,312 @@
+use cidr_utils::cidr::IpCidr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const NO_CHILD: u32 = 0;
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    children: [u32; 2],
+    value: Option<T>,
+}
+
+impl<T> Node<T> {
+    fn empty() -> Self {
+        Self {
+            children: [NO_CHILD; 2],
+            value: None,
+        }
+    }
+}
+
+/// Binary prefix trie over a single address family.
+///
+/// Nodes live in one arena so that the whole structure is a couple of flat
+/// allocations; index `0` is the root and doubles as the "no child" marker.
+#[derive(Debug, Clone)]
+struct FamilyTrie<T> {
+    nodes: Vec<Node<T>>,
+    width: u8,
+}
+
+impl<T> FamilyTrie<T> {
+    fn new(width: u8) -> Self {
+        Self {
+            nodes: vec![Node::empty()],
+            width,
+        }
+    }
+
+    fn bit(&self, key: u128, depth: u8) -> usize {
+        ((key >> (self.width - 1 - depth)) & 1) as usize
+    }
+
+    /// Value slot for the network, creating the path to it if needed.
+    fn slot(&mut self, key: u128, prefix_len: u8) -> &mut Option<T> {
+        let mut node = 0;
+        for depth in 0..prefix_len {
+            let bit = self.bit(key, depth);
+            let next = self.nodes[node].children[bit];
+            node = if next == NO_CHILD {
+                let index = self.nodes.len();
+                self.nodes.push(Node::empty());
+                self.nodes[node].children[bit] = index as u32;
+                index
+            } else {
+                next as usize
+            };
+        }
+        &mut self.nodes[node].value
+    }
+
+    fn matches(&self, key: u128) -> Vec<(&T, u8)> {
+        let mut node = 0;
+        let mut found: Vec<_> = self.nodes[0].value.iter().map(|v| (v, 0)).collect();
+        for depth in 0..self.width {
+            let next = self.nodes[node].children[self.bit(key, depth)];
+            if next == NO_CHILD {
+                break;
+            }
+            node = next as usize;
+            if let Some(value) = &self.nodes[node].value {
+                found.push((value, depth + 1));
+            }
+        }
+        found
+    }
+
+    /// Stored `(key, prefix_len, value)` triples in address order, shorter
+    /// prefixes before the networks nested in them.
+    fn entries(&self) -> Vec<(u128, u8, &T)> {
+        let mut entries = Vec::new();
+        let mut stack = vec![(0usize, 0u128, 0u8)];
+        while let Some((node, key, depth)) = stack.pop() {
+            if let Some(value) = &self.nodes[node].value {
+                entries.push((key, depth, value));
+            }
+            // Push the 1 branch first so the 0 branch is visited first.
+            for bit in [1, 0] {
+                let child = self.nodes[node].children[bit];
+                if child != NO_CHILD {
+                    let key = key | ((bit as u128) << (self.width - 1 - depth));
+                    stack.push((child as usize, key, depth + 1));
+                }
+            }
+        }
+        entries
+    }
+
+    fn raw_nodes(&self) -> Vec<RawNode<&T>> {
+        self.nodes
+            .iter()
+            .map(|node| (node.children, node.value.as_ref()))
+            .collect()
+    }
+
+    fn from_raw_nodes(
+        width: u8,
+        raw: Vec<RawNode<T>>,
+        valid: impl Fn(u128, u8, &T) -> bool,
+    ) -> Option<Self> {
+        let count = raw.len();
+        if count == 0 || count > u32::MAX as usize {
+            return None;
+        }
+        // Walk the arena from the root: every other node has to be the child
+        // of exactly one node and sit at most `width` bits deep, so the arena
+        // is a tree that no walk can loop in or descend past the last bit.
+        let mut reached = vec![false; count];
+        reached[0] = true;
+        let mut stack = vec![(0usize, 0u128, 0u8)];
+        while let Some((node, key, depth)) = stack.pop() {
+            let (children, value) = &raw[node];
+            if value
+                .as_ref()
+                .is_some_and(|value| !valid(key, depth, value))
+            {
+                return None;
+            }
+            for (bit, &child) in children.iter().enumerate() {
+                if child == NO_CHILD {
+                    continue;
+                }
+                let child = child as usize;
+                if depth == width || child >= count || reached[child] {
+                    return None;
+                }
+                reached[child] = true;
+                let key = key | ((bit as u128) << (width - 1 - depth));
+                stack.push((child, key, depth + 1));
+            }
+        }
+        if reached.contains(&false) {
+            return None;
+        }
+        let nodes = raw
+            .into_iter()
+            .map(|(children, value)| Node { children, value })
+            .collect();
+        Some(Self { nodes, width })
+    }
+
+    fn longest_match(&self, key: u128) -> Option<(&T, u8)> {
+        let mut node = 0;
+        let mut best = self.nodes[0].value.as_ref().map(|v| (v, 0));
+        for depth in 0..self.width {
+            let next = self.nodes[node].children[self.bit(key, depth)];
+            if next == NO_CHILD {
+                break;
+            }
+            node = next as usize;
+            if let Some(value) = &self.nodes[node].value {
+                best = Some((value, depth + 1));
+            }
+        }
+        best
+    }
+}
+
+/// Arena node as `(children, value)`, child `0` meaning "none".
+pub(crate) type RawNode<T> = ([u32; 2], Option<T>);
+
+/// Longest-prefix-match table for IPv4 and IPv6 networks.
+///
+/// Lookups cost at most one step per address bit, independent of how many
+/// prefixes were inserted or in which order.
+#[derive(Debug, Clone)]
+pub struct IpTrie<T> {
+    v4: FamilyTrie<T>,
+    v6: FamilyTrie<T>,
+    len: usize,
+}
+
+impl<T> Default for IpTrie<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> IpTrie<T> {
+    pub fn new() -> Self {
+        Self {
+            v4: FamilyTrie::new(32),
+            v6: FamilyTrie::new(128),
+            len: 0,
+        }
+    }
+
+    /// Inserts `value` for `cidr`, returning the value previously stored for
+    /// exactly the same network, if any.
+    pub fn insert(&mut self, cidr: &IpCidr, value: T) -> Option<T> {
+        let previous = self.slot(cidr).replace(value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    /// Returns the value stored for exactly `cidr`, inserting `default()`
+    /// first if there is none.
+    pub fn get_or_insert_with(&mut self, cidr: &IpCidr, default: impl FnOnce() -> T) -> &mut T {
+        if self.slot(cidr).is_none() {
+            self.len += 1;
+        }
+        self.slot(cidr).get_or_insert_with(default)
+    }
+
+    fn slot(&mut self, cidr: &IpCidr) -> &mut Option<T> {
+        let prefix_len = cidr.network_length();
+        match cidr.first_address() {
+            IpAddr::V4(addr) => self.v4.slot(u32::from(addr) as u128, prefix_len),
+            IpAddr::V6(addr) => self.v6.slot(u128::from(addr), prefix_len),
+        }
+    }
+
+    /// Returns the value of the most specific network containing `ip`.
+    pub fn longest_match(&self, ip: IpAddr) -> Option<&T> {
+        self.longest_match_with_len(ip).map(|(value, _)| value)
+    }
+
+    /// Like [`IpTrie::longest_match`], also returning the matched prefix length.
+    pub fn longest_match_with_len(&self, ip: IpAddr) -> Option<(&T, u8)> {
+        match ip {
+            IpAddr::V4(addr) => self.v4.longest_match(u32::from(addr) as u128),
+            IpAddr::V6(addr) => self.v6.longest_match(u128::from(addr)),
+        }
+    }
+
+    /// Returns every network containing `ip` with its prefix length, from
+    /// the least to the most specific.
+    pub fn matches(&self, ip: IpAddr) -> Vec<(&T, u8)> {
+        match ip {
+            IpAddr::V4(addr) => self.v4.matches(u32::from(addr) as u128),
+            IpAddr::V6(addr) => self.v6.matches(u128::from(addr)),
+        }
+    }
+
+    /// Every stored network with its value, IPv4 first, in address order
+    /// with shorter prefixes before the networks nested in them.
+    pub fn iter(&self) -> impl Iterator<Item = (IpCidr, &T)> + '_ {
+        let v4 = self.v4.entries().into_iter().map(|(key, len, value)| {
+            let addr = IpAddr::V4(Ipv4Addr::from(key as u32));
+            (IpCidr::new(addr, len).expect("stored network"), value)
+        });
+        let v6 = self.v6.entries().into_iter().map(|(key, len, value)| {
+            let addr = IpAddr::V6(Ipv6Addr::from(key));
+            (IpCidr::new(addr, len).expect("stored network"), value)
+        });
+        v4.chain(v6)
+    }
+
+    /// Number of distinct networks stored.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The IPv4 and IPv6 arenas, root first, for serialization.
+    pub(crate) fn raw_nodes(&self) -> [Vec<RawNode<&T>>; 2] {
+        [self.v4.raw_nodes(), self.v6.raw_nodes()]
+    }
+
+    /// Rebuilds a trie from [`IpTrie::raw_nodes`] output. Returns `None`
+    /// unless both arenas form a tree no deeper than the address width, or
+    /// if `valid` rejects a value for the network its node stands for.
+    pub(crate) fn from_raw_nodes(
+        v4: Vec<RawNode<T>>,
+        v6: Vec<RawNode<T>>,
+        valid: impl Fn(&IpCidr, &T) -> bool,
+    ) -> Option<Self> {
+        let v4 = FamilyTrie::from_raw_nodes(32, v4, |key, len, value| {
+            let addr = IpAddr::V4(Ipv4Addr::from(key as u32));
+            IpCidr::new(addr, len).is_ok_and(|cidr| valid(&cidr, value))
+        })?;
+        let v6 = FamilyTrie::from_raw_nodes(128, v6, |key, len, value| {
+            let addr = IpAddr::V6(Ipv6Addr::from(key));
+            IpCidr::new(addr, len).is_ok_and(|cidr| valid(&cidr, value))
+        })?;
+        let len = [&v4, &v6]
+            .iter()
+            .flat_map(|family| &family.nodes)
+            .filter(|node| node.value.is_some())
+            .count();
+        Some(Self { v4, v6, len })
+    }
+}
+
+/// Network of length `prefix_len` that contains `ip`.
+pub(crate) fn network(ip: IpAddr, prefix_len: u8) -> Option<IpCidr> {
+    let first = match ip {
+        IpAddr::V4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+        }
+        IpAddr::V6(addr) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+        }
+    };
+    IpCidr::new(first, prefix_len).ok()
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{fixture_db, write};
+use geo_ip::{AddressForm, IpDatabase, Verdict};
+use std::net::IpAddr;
+
+const FEED: &str = "\
+cidr,asn,provider,category
+192.0.2.0/24,64500,Example VPN,vpn
+2002:c000:201::/48,64501,Example 6to4 Site,hosting
+";
+
+fn matched(ip_db: &IpDatabase, ip: &str) -> Option<(String, String, AddressForm)> {
+    let m = ip_db.lookup(ip.parse().unwrap())?;
+    Some((m.cidr.to_string(), m.address.to_string(), m.form))
+}
+
+#[test]
+fn ipv4_in_ipv6_notation_matches_ipv4_ranges() {
+    let ip_db = fixture_db("address-mapped.csv", FEED);
+
+    let direct = matched(&ip_db, "192.0.2.10").unwrap();
+    assert_eq!(direct.2, AddressForm::Direct);
+    assert_eq!(direct.1, "192.0.2.10");
+
+    for (ip, form) in [
+        ("::ffff:192.0.2.10", AddressForm::Ipv4Mapped),
+        ("::192.0.2.10", AddressForm::Ipv4Compatible),
+        ("64:ff9b::192.0.2.10", AddressForm::Nat64),
+        // Teredo stores the client address with its bits inverted.
+        ("2001:0:4136:e378:8000:63bf:3fff:fdf5", AddressForm::Teredo),
+        ("2002:c000:20a::1", AddressForm::SixToFour),
+    ] {
+        assert_eq!(
+            matched(&ip_db, ip),
+            Some(("192.0.2.0/24".into(), "192.0.2.10".into(), form)),
+            "{}",
+            ip
+        );
+    }
+
+    for ip in ["::", "::1", "::ffff:198.51.100.1", "2001:db8::1"] {
+        assert_eq!(matched(&ip_db, ip), None, "{}", ip);
+    }
+}
+
+#[test]
+fn listed_ipv6_address_wins_over_its_embedded_ipv4() {
+    let ip_db = fixture_db("address-6to4.csv", FEED);
+
+    let (cidr, address, form) = matched(&ip_db, "2002:c000:201::1").unwrap();
+    assert_eq!(cidr, "2002:c000:201::/48");
+    assert_eq!(address, "2002:c000:201::1");
+    assert_eq!(form, AddressForm::Direct);
+}
+
+#[test]
+fn overrides_apply_to_mapped_addresses() {
+    let mut ip_db = fixture_db("address-override.csv", FEED);
+    let overrides = write(
+        "address-overrides.csv",
+        "cidr,verdict,reason,expires\n192.0.2.10/32,allow,office,\n",
+    );
+    ip_db.load_overrides(&overrides).unwrap();
+    std::fs::remove_file(overrides).unwrap();
+
+    let mapped: IpAddr = "::ffff:192.0.2.10".parse().unwrap();
+    let operator_override = ip_db.check_override(mapped).unwrap();
+    assert_eq!(operator_override.verdict, Verdict::Allow);
+    assert!(!ip_db.is_vpn_ip(mapped));
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::{fixture_db, write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::num::NonZeroUsize;
+
+const FEED: &str = "\
+cidr,asn,provider
+10.0.0.0/8,64500,Example VPN
+";
+
+#[test]
+fn hits_and_misses_are_both_cached_and_counted() {
+    let ip_db = fixture_db("cache-counts.csv", FEED);
+    let listed: IpAddr = "10.1.2.3".parse().unwrap();
+    let clean: IpAddr = "192.0.2.1".parse().unwrap();
+
+    for _ in 0..3 {
+        assert!(ip_db.lookup(listed).is_some());
+        assert!(ip_db.lookup(clean).is_none());
+    }
+
+    let stats = ip_db.cache_stats();
+    assert_eq!((stats.hits, stats.misses, stats.evictions), (4, 2, 0));
+    assert_eq!(stats.len, 2);
+    // The default 1000 rounded up to 16 shards of 63.
+    assert_eq!(stats.capacity, 1008);
+    assert!((stats.hit_ratio() - 4.0 / 6.0).abs() < 1e-9);
+}
+
+#[test]
+fn capacity_is_configurable_and_evictions_are_counted() {
+    let mut ip_db = fixture_db("cache-evictions.csv", FEED);
+    ip_db.lookup("10.1.2.3".parse().unwrap());
+    ip_db.set_cache_capacity(NonZeroUsize::new(16).unwrap());
+    assert_eq!(
+        ip_db.cache_stats().misses,
+        0,
+        "resizing resets the counters"
+    );
+
+    for host in 0..100u32 {
+        ip_db.lookup(IpAddr::V4(Ipv4Addr::from(0xc000_0200 + host)));
+    }
+
+    let stats = ip_db.cache_stats();
+    assert_eq!(stats.capacity, 16);
+    assert_eq!(stats.misses, 100);
+    assert!(stats.len <= 16, "{:?}", stats);
+    assert_eq!(stats.evictions, 100 - stats.len as u64);
+}
+
+#[test]
+fn merging_a_feed_invalidates_cached_misses() {
+    let mut ip_db = fixture_db("cache-invalidate.csv", FEED);
+    let ip: IpAddr = "192.0.2.1".parse().unwrap();
+    assert!(ip_db.lookup(ip).is_none());
+
+    let extra = write("cache-invalidate-extra.txt", "192.0.2.0/24\n");
+    ip_db
+        .merge_list(
+            &extra,
+            geo_ip::ListFormat::Cidr,
+            &Default::default(),
+            Default::default(),
+        )
+        .unwrap();
+    std::fs::remove_file(extra).unwrap();
+
+    assert!(ip_db.lookup(ip).is_some());
+
+    let exit: IpAddr = "198.51.100.7".parse().unwrap();
+    assert!(ip_db.lookup(exit).is_none());
+    let tor = write("cache-invalidate-tor.txt", "198.51.100.7\n");
+    ip_db.merge_tor_exit_list(&tor, None).unwrap();
+    std::fs::remove_file(tor).unwrap();
+
+    assert!(ip_db.lookup(exit).is_some());
+    assert_eq!(ip_db.cache_stats().evictions, 0);
+}
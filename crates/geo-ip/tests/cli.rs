@@ -0,0 +1,118 @@
+mod common;
+
+use common::temp_path;
+use std::process::{Command, Output};
+
+fn geo_ip(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_geo-ip"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn diff_reports_added_removed_and_reclassified_ranges() {
+    let old = temp_path("cli-old.csv");
+    let new = temp_path("cli-new.csv");
+    std::fs::write(
+        &old,
+        "cidr,asn,provider,category\n\
+         10.0.0.0/8,64500,Example VPN,vpn\n\
+         10.1.0.0/16,64501,Example Hosting,hosting\n\
+         192.0.2.0/24,64502,Example Proxy,proxy\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &new,
+        "cidr,asn,provider,category\n\
+         10.0.0.0/8,64500,Example VPN,vpn\n\
+         10.1.0.0/16,64501,Example Hosting,vpn\n\
+         198.51.100.0/24,64503,Example Tor,tor\n",
+    )
+    .unwrap();
+
+    let output = geo_ip(&["diff", old.to_str().unwrap(), new.to_str().unwrap()]);
+    assert!(output.status.success());
+    let lines: Vec<_> = stdout(&output).lines().map(String::from).collect();
+    assert_eq!(
+        lines,
+        [
+            "~ 10.1.0.0/16  hosting Example Hosting (AS64501) -> vpn Example Hosting (AS64501)",
+            "- 192.0.2.0/24  proxy Example Proxy (AS64502)",
+            "+ 198.51.100.0/24  tor Example Tor (AS64503)",
+            "1 added, 1 removed, 1 reclassified",
+        ]
+    );
+
+    let output = geo_ip(&["lookup", "--db", new.to_str().unwrap(), "10.1.2.3"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("10.1.2.3: vpn\n  range       10.1.0.0/16\n"));
+
+    let output = geo_ip(&["stats", "--db", old.to_str().unwrap()]);
+    assert!(stdout(&output).contains("IPv4 space covered: 0.391%"));
+
+    std::fs::remove_file(old).unwrap();
+    std::fs::remove_file(new).unwrap();
+}
+
+#[test]
+fn validate_fails_on_invalid_rows() {
+    let feed = temp_path("cli-validate.ipset");
+    std::fs::write(
+        &feed,
+        "add blocklist 192.0.2.0/24\nadd blocklist 192.0.2.300\n",
+    )
+    .unwrap();
+    let path = feed.to_str().unwrap();
+
+    let output = geo_ip(&["validate", "--format", "ipset", path]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!(
+        "{}:2: skipped: invalid address: 192.0.2.300",
+        path
+    )));
+
+    std::fs::write(&feed, "add blocklist 192.0.2.0/24\n").unwrap();
+    assert!(geo_ip(&["validate", "--format", "ipset", path])
+        .status
+        .success());
+
+    std::fs::remove_file(feed).unwrap();
+}
+
+#[test]
+fn compile_applies_weight_to_the_following_feeds() {
+    let feed = temp_path("cli-compile.txt");
+    let snapshot = temp_path("cli-compile.bin");
+    std::fs::write(&feed, "192.0.2.0/24\n").unwrap();
+    let (feed_path, snapshot_path) = (feed.to_str().unwrap(), snapshot.to_str().unwrap());
+
+    let output = geo_ip(&[
+        "compile",
+        snapshot_path,
+        "--format",
+        "cidr",
+        "--weight",
+        "0.5",
+        feed_path,
+    ]);
+    assert!(output.status.success());
+
+    let ip_db = geo_ip::IpDatabase::load_snapshot(&snapshot).unwrap();
+    let m = ip_db.lookup("192.0.2.1".parse().unwrap()).unwrap();
+    assert_eq!(&*m.source, feed_path);
+    assert_eq!(m.sources[0].weight, 0.5);
+
+    for weight in ["2", "-0.1", "heavy"] {
+        let output = geo_ip(&["compile", snapshot_path, "--weight", weight, feed_path]);
+        assert!(!output.status.success(), "--weight {}", weight);
+    }
+
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(snapshot).unwrap();
+}
@@ -0,0 +1,194 @@
+//! Minimal MaxMind DB writer for generating test fixtures.
+//!
+//! Produces IPv6 trees with 24-bit records; IPv4 networks are stored in the
+//! `::/96` subtree the way MaxMind's own databases do. Inserted networks must
+//! not overlap.
+
+use cidr_utils::cidr::IpCidr;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Str(String),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.into())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::F64(n)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Self {
+        Value::U32(n)
+    }
+}
+
+/// Builds a `Value::Map` from `key => value` pairs.
+#[macro_export]
+macro_rules! mmdb_map {
+    ($($key:expr => $value:expr),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut map = ::std::collections::BTreeMap::new();
+        $(map.insert(String::from($key), $crate::common::mmdb_writer::Value::from($value));)*
+        $crate::common::mmdb_writer::Value::Map(map)
+    }};
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Record {
+    Empty,
+    Node(usize),
+    Data(usize),
+}
+
+pub struct MmdbWriter {
+    database_type: String,
+    nodes: Vec<[Record; 2]>,
+    data: Vec<u8>,
+}
+
+impl MmdbWriter {
+    pub fn new(database_type: &str) -> Self {
+        Self {
+            database_type: database_type.into(),
+            nodes: vec![[Record::Empty; 2]],
+            data: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, cidr: &str, value: &Value) {
+        let cidr: IpCidr = cidr.parse().expect("fixture CIDR");
+        let (key, prefix_len) = match cidr.first_address() {
+            IpAddr::V4(addr) => (u32::from(addr) as u128, cidr.network_length() + 96),
+            IpAddr::V6(addr) => (u128::from(addr), cidr.network_length()),
+        };
+        assert!(prefix_len > 0, "cannot store a value at the tree root");
+
+        let offset = self.data.len();
+        encode(value, &mut self.data);
+
+        let mut node = 0;
+        for depth in 0..prefix_len - 1 {
+            let bit = ((key >> (127 - depth)) & 1) as usize;
+            node = match self.nodes[node][bit] {
+                Record::Node(next) => next,
+                Record::Empty => {
+                    self.nodes.push([Record::Empty; 2]);
+                    let next = self.nodes.len() - 1;
+                    self.nodes[node][bit] = Record::Node(next);
+                    next
+                }
+                Record::Data(_) => panic!("overlapping fixture networks"),
+            };
+        }
+        let bit = ((key >> (128 - prefix_len)) & 1) as usize;
+        self.nodes[node][bit] = Record::Data(offset);
+    }
+
+    pub fn write(&self, path: &Path) {
+        let node_count = self.nodes.len();
+        let mut out = Vec::new();
+
+        for node in &self.nodes {
+            for record in node {
+                let value = match *record {
+                    Record::Empty => node_count,
+                    Record::Node(next) => next,
+                    Record::Data(offset) => node_count + 16 + offset,
+                };
+                out.extend_from_slice(&(value as u32).to_be_bytes()[1..]);
+            }
+        }
+        out.extend_from_slice(&[0; 16]);
+        out.extend_from_slice(&self.data);
+
+        out.extend_from_slice(b"\xab\xcd\xefMaxMind.com");
+        let metadata = mmdb_map! {
+            "node_count" => node_count as u32,
+            "record_size" => Value::U16(24),
+            "ip_version" => Value::U16(6),
+            "database_type" => self.database_type.as_str(),
+            "languages" => Value::Array(vec!["en".into()]),
+            "binary_format_major_version" => Value::U16(2),
+            "binary_format_minor_version" => Value::U16(0),
+            "build_epoch" => Value::U64(0),
+            "description" => mmdb_map! { "en" => "test fixture" },
+        };
+        encode(&metadata, &mut out);
+
+        std::fs::write(path, out).expect("write fixture");
+    }
+}
+
+fn encode(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Str(s) => {
+            control(2, s.len(), out);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::U16(n) => uint(5, *n as u64, out),
+        Value::U32(n) => uint(6, *n as u64, out),
+        Value::U64(n) => uint(9, *n, out),
+        Value::F64(n) => {
+            control(3, 8, out);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+        Value::Bool(b) => control(14, *b as usize, out),
+        Value::Array(items) => {
+            control(11, items.len(), out);
+            items.iter().for_each(|item| encode(item, out));
+        }
+        Value::Map(map) => {
+            control(7, map.len(), out);
+            for (key, value) in map {
+                encode(&Value::Str(key.clone()), out);
+                encode(value, out);
+            }
+        }
+    }
+}
+
+fn uint(type_num: u8, n: u64, out: &mut Vec<u8>) {
+    let bytes = n.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count();
+    control(type_num, bytes.len() - skip, out);
+    out.extend_from_slice(&bytes[skip..]);
+}
+
+fn control(type_num: u8, size: usize, out: &mut Vec<u8>) {
+    let (size_bits, extra): (u8, Vec<u8>) = match size {
+        0..=28 => (size as u8, vec![]),
+        29..=284 => (29, vec![(size - 29) as u8]),
+        285..=65820 => (30, ((size - 285) as u16).to_be_bytes().to_vec()),
+        _ => (31, ((size - 65821) as u32).to_be_bytes()[1..].to_vec()),
+    };
+    if type_num <= 7 {
+        out.push(type_num << 5 | size_bits);
+    } else {
+        out.push(size_bits);
+        out.push(type_num - 7);
+    }
+    out.extend_from_slice(&extra);
+}
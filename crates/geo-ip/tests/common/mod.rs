@@ -0,0 +1,27 @@
+#![allow(dead_code)]
+
+pub mod mmdb_writer;
+
+use geo_ip::IpDatabase;
+use std::path::PathBuf;
+
+/// Path in the system temp dir that is unique to this test process.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("geo-ip-{}-{}", std::process::id(), name))
+}
+
+/// Writes `contents` to [`temp_path`] and returns the path.
+pub fn write(name: &str, contents: &str) -> PathBuf {
+    let path = temp_path(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// The database loaded from the CSV `feed`, written to a temp file named
+/// `name` for the load.
+pub fn fixture_db(name: &str, feed: &str) -> IpDatabase {
+    let path = write(name, feed);
+    let ip_db = IpDatabase::load_from_csv(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    ip_db
+}
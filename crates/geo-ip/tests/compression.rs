@@ -0,0 +1,88 @@
+mod common;
+
+use common::temp_path;
+use geo_ip::{Category, IpDatabase, ListFormat, LoadMode, SourceOptions};
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+const PROBES: [&str; 4] = ["10.1.2.3", "10.200.0.1", "2001:db8::1", "192.0.2.1"];
+
+fn assert_same_lookups(ip_db: &IpDatabase, plain: &IpDatabase) {
+    for ip in PROBES {
+        let ip = ip.parse().unwrap();
+        let (got, want) = (ip_db.lookup(ip), plain.lookup(ip));
+        assert_eq!(got.as_ref().map(|m| m.cidr), want.as_ref().map(|m| m.cidr));
+        assert_eq!(got.and_then(|m| m.category), want.and_then(|m| m.category));
+    }
+}
+
+#[test]
+fn gzip_and_zstd_feeds_load_like_the_plain_feed() {
+    let plain = IpDatabase::load_from_csv(fixture("feed.csv")).unwrap();
+
+    for name in ["feed.csv.gz", "feed.csv.zst"] {
+        let ip_db = IpDatabase::load_from_csv(fixture(name)).unwrap();
+        assert_same_lookups(&ip_db, &plain);
+
+        let (ip_db, report) = IpDatabase::load_from_file(fixture(name), LoadMode::Strict).unwrap();
+        assert_eq!(report.loaded, 3, "{}", name);
+        assert_same_lookups(&ip_db, &plain);
+    }
+}
+
+#[test]
+fn codec_is_detected_from_magic_bytes_without_an_extension() {
+    let plain = IpDatabase::load_from_csv(fixture("feed.csv")).unwrap();
+
+    for name in ["feed.csv.gz", "feed.csv.zst"] {
+        let renamed = temp_path(&format!("{}-feed", name.replace('.', "-")));
+        std::fs::copy(fixture(name), &renamed).unwrap();
+        let ip_db = IpDatabase::load_from_csv(&renamed).unwrap();
+        assert_same_lookups(&ip_db, &plain);
+        std::fs::remove_file(renamed).unwrap();
+    }
+}
+
+#[test]
+fn compressed_lists_take_the_provider_from_the_uncompressed_name() {
+    let mut ip_db = IpDatabase::default();
+    let options = SourceOptions {
+        category: Category::Proxy,
+        ..SourceOptions::default()
+    };
+    let report = ip_db
+        .merge_list(
+            fixture("proxies.netset.gz"),
+            ListFormat::Cidr,
+            &options,
+            LoadMode::Strict,
+        )
+        .unwrap();
+    assert_eq!(report.loaded, 2);
+
+    let m = ip_db.lookup("203.0.113.7".parse().unwrap()).unwrap();
+    assert_eq!(&*m.provider, "proxies");
+    assert_eq!(m.category, Some(Category::Proxy));
+}
+
+#[test]
+fn compressed_snapshots_are_detected() {
+    let snapshot = temp_path("compressed-snapshot.bin");
+    let compressed = temp_path("compressed-snapshot.bin.zst");
+    let plain = IpDatabase::load_from_csv(fixture("feed.csv")).unwrap();
+    plain.write_snapshot(&snapshot).unwrap();
+    let bytes = std::fs::read(&snapshot).unwrap();
+    std::fs::write(&compressed, zstd::encode_all(&bytes[..], 3).unwrap()).unwrap();
+
+    let (ip_db, _) = IpDatabase::load_from_file(&compressed, LoadMode::Strict).unwrap();
+    assert!(ip_db.snapshot_metadata().is_some());
+    assert_same_lookups(&ip_db, &plain);
+
+    std::fs::remove_file(snapshot).unwrap();
+    std::fs::remove_file(compressed).unwrap();
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use common::write;
+use geo_ip::{Category, GeoIpError, IpDatabase, ListFormat, LoadMode, SourceOptions};
+
+fn proxy_source() -> SourceOptions {
+    SourceOptions {
+        category: Category::Proxy,
+        weight: 1.0,
+    }
+}
+
+fn import(name: &str, contents: &str, format: ListFormat) -> (IpDatabase, geo_ip::LoadReport) {
+    let path = write(name, contents);
+    let mut ip_db = IpDatabase::default();
+    let report = ip_db
+        .merge_list(&path, format, &proxy_source(), LoadMode::Lenient)
+        .unwrap();
+    std::fs::remove_file(path).unwrap();
+    (ip_db, report)
+}
+
+fn listed(ip_db: &IpDatabase, ip: &str) -> Option<String> {
+    ip_db
+        .lookup(ip.parse().unwrap())
+        .map(|m| m.cidr.to_string())
+}
+
+#[test]
+fn cidr_list_accepts_networks_and_bare_addresses() {
+    let (ip_db, report) = import(
+        "list.netset",
+        "# firehol style\n192.0.2.0/24\n198.51.100.7 # single host\n\nnot-an-ip\n2001:db8::/48\n",
+        ListFormat::Cidr,
+    );
+
+    assert_eq!(report.loaded, 3);
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].row, 5);
+    assert_eq!(listed(&ip_db, "192.0.2.9").as_deref(), Some("192.0.2.0/24"));
+    assert_eq!(
+        listed(&ip_db, "198.51.100.7").as_deref(),
+        Some("198.51.100.7")
+    );
+
+    let hit = ip_db.lookup("2001:db8::1".parse().unwrap()).unwrap();
+    assert_eq!(hit.category, Some(Category::Proxy));
+    assert_eq!(
+        &*hit.provider,
+        format!("geo-ip-{}-list", std::process::id())
+    );
+    assert!(hit.source.ends_with("list.netset"));
+}
+
+#[test]
+fn ranges_are_split_into_minimal_networks() {
+    let (ip_db, report) = import(
+        "ranges.txt",
+        "10.0.0.1-10.0.0.10\n2001:db8::-2001:db8::ffff\n10.0.0.20-10.0.0.19\n10.0.0.1-2001:db8::1\n",
+        ListFormat::Range,
+    );
+
+    // 10.0.0.1/32, 10.0.0.2/31, 10.0.0.4/30, 10.0.0.8/31, 10.0.0.10/32 and
+    // 2001:db8::/112.
+    assert_eq!(report.loaded, 6);
+    assert_eq!(
+        report.skipped.iter().map(|s| s.row).collect::<Vec<_>>(),
+        [3, 4]
+    );
+    assert_eq!(listed(&ip_db, "10.0.0.6").as_deref(), Some("10.0.0.4/30"));
+    assert_eq!(listed(&ip_db, "10.0.0.10").as_deref(), Some("10.0.0.10"));
+    assert_eq!(listed(&ip_db, "10.0.0.11"), None);
+    assert_eq!(listed(&ip_db, "10.0.0.0"), None);
+    assert_eq!(
+        listed(&ip_db, "2001:db8::abcd").as_deref(),
+        Some("2001:db8::/112")
+    );
+
+    let (ip_db, report) = import(
+        "everything.txt",
+        "0.0.0.0-255.255.255.255\n",
+        ListFormat::Range,
+    );
+    assert_eq!(report.loaded, 1);
+    assert_eq!(listed(&ip_db, "203.0.113.1").as_deref(), Some("0.0.0.0/0"));
+}
+
+#[test]
+fn ipset_save_reads_add_lines_only() {
+    let (ip_db, report) = import(
+        "ipset.save",
+        "create blocklist hash:net family inet hashsize 1024 maxelem 65536\n\
+         add blocklist 192.0.2.0/24\n\
+         add blocklist 198.51.100.1 timeout 3600\n\
+         add blocklist 203.0.113.0-203.0.113.3\n\
+         add blocklist\n",
+        ListFormat::Ipset,
+    );
+
+    assert_eq!(report.loaded, 3);
+    assert_eq!(report.skipped[0].row, 5);
+    assert_eq!(
+        listed(&ip_db, "203.0.113.2").as_deref(),
+        Some("203.0.113.0/30")
+    );
+    assert_eq!(
+        listed(&ip_db, "198.51.100.1").as_deref(),
+        Some("198.51.100.1")
+    );
+}
+
+#[test]
+fn nftables_set_elements_span_lines() {
+    let (ip_db, report) = import(
+        "blocklist.nft",
+        "table inet filter {\n\
+         \tset blocklist {\n\
+         \t\ttype ipv4_addr\n\
+         \t\tflags interval\n\
+         \t\telements = { 192.0.2.0/24, 198.51.100.7 timeout 1h expires 59m,\n\
+         \t\t\t     203.0.113.8-203.0.113.15, bogus }\n\
+         \t}\n\
+         }\n",
+        ListFormat::Nftables,
+    );
+
+    assert_eq!(report.loaded, 3);
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].row, 6);
+    assert_eq!(
+        listed(&ip_db, "203.0.113.9").as_deref(),
+        Some("203.0.113.8/29")
+    );
+    assert_eq!(
+        listed(&ip_db, "198.51.100.7").as_deref(),
+        Some("198.51.100.7")
+    );
+}
+
+#[test]
+fn strict_mode_reports_the_failing_line() {
+    let path = write("strict.netset", "192.0.2.0/24\n192.0.2.0/33\n");
+
+    let err = IpDatabase::default()
+        .merge_list(&path, ListFormat::Cidr, &proxy_source(), LoadMode::Strict)
+        .unwrap_err();
+    assert!(matches!(err, GeoIpError::InvalidListEntry { line: 2, .. }));
+
+    std::fs::remove_file(path).unwrap();
+}
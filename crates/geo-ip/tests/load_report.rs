@@ -0,0 +1,54 @@
+mod common;
+
+use common::write;
+use geo_ip::{GeoIpError, IpDatabase, LoadMode};
+
+const FEED: &str = "\
+cidr,asn,provider,category
+10.0.0.0/8,64500,Example VPN,vpn
+not-a-cidr,64500,Broken,vpn
+10.1.0.0/16,64501,Example Hosting,hosting
+192.0.2.0/24,As64502,Prefixed ASN,vpn
+10.1.0.0/16,64503,Example Hosting Again,hosting
+198.51.100.0/24,64504,Too,many,columns
+203.0.113.0/24,64505,Unknown Category,darknet
+198.18.0.0/15,ASN64506,Broken ASN,vpn
+";
+
+#[test]
+fn lenient_load_skips_invalid_rows_and_reports_them() {
+    let path = write("lenient.csv", FEED);
+    let (ip_db, report) = IpDatabase::load_from_csv_with_report(&path, LoadMode::Lenient).unwrap();
+
+    assert_eq!(report.loaded, 4);
+    assert_eq!(report.duplicates, 1);
+    let rows: Vec<_> = report.skipped.iter().map(|s| s.row).collect();
+    assert_eq!(rows, [3, 7, 8, 9]);
+    assert!(report.skipped[0].reason.contains("not-a-cidr"));
+    assert!(report.skipped[3].reason.contains("ASN64506"));
+
+    assert_eq!(report.overlaps.len(), 1);
+    assert_eq!(report.overlaps[0].cidr.to_string(), "10.1.0.0/16");
+    assert_eq!(report.overlaps[0].within.to_string(), "10.0.0.0/8");
+
+    let hit = ip_db.lookup("10.1.2.3".parse().unwrap()).unwrap();
+    assert_eq!(&*hit.provider, "Example Hosting Again");
+    // The AS prefix is optional, as in the ASN table and pfx2as formats.
+    assert_eq!(
+        ip_db.lookup("192.0.2.1".parse().unwrap()).unwrap().asn,
+        64502
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn strict_load_fails_on_first_invalid_row() {
+    let path = write("strict.csv", FEED);
+
+    let err = IpDatabase::load_from_csv(&path).unwrap_err();
+    assert!(matches!(err, GeoIpError::InvalidCidr(ref cidr) if cidr == "not-a-cidr"));
+    assert!(IpDatabase::load_from_csv_with_report(&path, LoadMode::Strict).is_err());
+
+    std::fs::remove_file(path).unwrap();
+}
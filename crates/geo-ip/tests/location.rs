@@ -0,0 +1,106 @@
+mod common;
+
+use common::mmdb_writer::{MmdbWriter, Value};
+use common::temp_path;
+use geo_ip::{Coordinates, GeoIpError, IpDatabase};
+
+const FEED: &str = "\
+cidr,asn,provider,country,region,latitude,longitude
+10.0.0.0/8,64500,Example VPN,nl,NH,52.37,4.89
+10.1.0.0/16,64501,Example Hosting,,,,
+";
+
+fn city_fixture(tag: &str) -> std::path::PathBuf {
+    let mut city = MmdbWriter::new("GeoLite2-City");
+    city.insert(
+        "10.1.0.0/16",
+        &mmdb_map! {
+            "country" => mmdb_map! { "iso_code" => "DE" },
+            "subdivisions" => Value::Array(vec![mmdb_map! { "iso_code" => "BE" }]),
+            "location" => mmdb_map! { "latitude" => 52.52, "longitude" => 13.40 },
+        },
+    );
+    city.insert(
+        "192.0.2.0/24",
+        &mmdb_map! { "country" => mmdb_map! { "iso_code" => "FR" } },
+    );
+    let path = temp_path(&format!("{tag}-city.mmdb"));
+    city.write(&path);
+    path
+}
+
+#[test]
+fn csv_columns_locate_ranges_and_survive_snapshots() {
+    let feed = temp_path("location-feed.csv");
+    let snapshot = temp_path("location.bin");
+    std::fs::write(&feed, FEED).unwrap();
+
+    let ip_db = IpDatabase::load_from_csv(&feed).unwrap();
+    let m = ip_db.lookup("10.2.0.1".parse().unwrap()).unwrap();
+    assert_eq!(m.country.as_deref(), Some("NL"));
+    assert_eq!(m.region.as_deref(), Some("NH"));
+    assert_eq!(m.coordinates, Coordinates::new(52.37, 4.89));
+
+    let bare = ip_db.lookup("10.1.0.1".parse().unwrap()).unwrap();
+    assert!(bare.location().is_empty());
+
+    ip_db.write_snapshot(&snapshot).unwrap();
+    let snapshot_db = IpDatabase::load_snapshot(&snapshot).unwrap();
+    let ip = "10.2.0.1".parse().unwrap();
+    assert_eq!(snapshot_db.lookup(ip), ip_db.lookup(ip));
+
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(snapshot).unwrap();
+}
+
+#[test]
+fn geo_database_fills_ranges_without_location_and_unlisted_addresses() {
+    let feed = temp_path("geo-feed.csv");
+    let city = city_fixture("geo");
+    std::fs::write(&feed, FEED).unwrap();
+
+    let mut ip_db = IpDatabase::load_from_csv(&feed).unwrap();
+    ip_db.load_geo_mmdb(&city).unwrap();
+
+    // The range's own columns win over the geo database.
+    let listed = ip_db.lookup("10.2.0.1".parse().unwrap()).unwrap();
+    assert_eq!(listed.country.as_deref(), Some("NL"));
+
+    let filled = ip_db.lookup("10.1.0.1".parse().unwrap()).unwrap();
+    assert_eq!(filled.country.as_deref(), Some("DE"));
+    assert_eq!(filled.region.as_deref(), Some("BE"));
+    assert_eq!(filled.coordinates, Coordinates::new(52.52, 13.40));
+
+    let unlisted = "192.0.2.1".parse().unwrap();
+    assert!(ip_db.lookup(unlisted).is_none());
+    let location = ip_db.locate(unlisted).unwrap();
+    assert_eq!(location.country.as_deref(), Some("FR"));
+    assert_eq!(location.coordinates, None);
+
+    assert!(ip_db.locate("198.51.100.1".parse().unwrap()).is_none());
+
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(city).unwrap();
+}
+
+#[test]
+fn invalid_location_columns_are_rejected() {
+    let feed = temp_path("bad-location.csv");
+    for (row, expected) in [
+        ("10.0.0.0/8,1,X,NLD,,,", "country"),
+        ("10.0.0.0/8,1,X,NL,,91,4", "coordinates"),
+        ("10.0.0.0/8,1,X,NL,,52,", "coordinates"),
+    ] {
+        std::fs::write(
+            &feed,
+            format!("cidr,asn,provider,country,region,latitude,longitude\n{row}\n"),
+        )
+        .unwrap();
+        let err = IpDatabase::load_from_csv(&feed).unwrap_err();
+        match expected {
+            "country" => assert!(matches!(err, GeoIpError::InvalidCountry(_)), "{row}"),
+            _ => assert!(matches!(err, GeoIpError::InvalidCoordinates(_)), "{row}"),
+        }
+    }
+    std::fs::remove_file(feed).unwrap();
+}
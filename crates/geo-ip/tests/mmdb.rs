@@ -0,0 +1,169 @@
+mod common;
+
+use common::mmdb_writer::{MmdbWriter, Value};
+use common::temp_path;
+use geo_ip::{Category, GeoIpError, IpDatabase, MmdbPaths};
+use std::path::PathBuf;
+
+struct Fixtures {
+    anonymous_ip: PathBuf,
+    asn: PathBuf,
+    city: PathBuf,
+}
+
+impl Fixtures {
+    fn generate(tag: &str) -> Self {
+        let mut anonymous_ip = MmdbWriter::new("GeoIP2-Anonymous-IP");
+        anonymous_ip.insert(
+            "198.51.100.0/24",
+            &mmdb_map! { "is_anonymous" => true, "is_anonymous_vpn" => true },
+        );
+        anonymous_ip.insert(
+            "203.0.113.7/32",
+            &mmdb_map! { "is_anonymous" => true, "is_tor_exit_node" => true },
+        );
+        anonymous_ip.insert(
+            "2001:db8:1::/48",
+            &mmdb_map! {
+                "is_anonymous" => true,
+                "is_hosting_provider" => true,
+                "is_public_proxy" => true,
+            },
+        );
+
+        let mut asn = MmdbWriter::new("GeoLite2-ASN");
+        asn.insert(
+            "198.51.0.0/16",
+            &mmdb_map! {
+                "autonomous_system_number" => 64500u32,
+                "autonomous_system_organization" => "Example VPN Hosting",
+            },
+        );
+        asn.insert(
+            "192.0.2.0/24",
+            &mmdb_map! {
+                "autonomous_system_number" => 64501u32,
+                "autonomous_system_organization" => "Example Broadband",
+            },
+        );
+
+        let mut city = MmdbWriter::new("GeoLite2-City");
+        city.insert(
+            "198.51.100.0/24",
+            &mmdb_map! { "country" => mmdb_map! { "iso_code" => "NL" } },
+        );
+        city.insert(
+            "192.0.2.0/24",
+            &mmdb_map! { "country" => mmdb_map! { "iso_code" => "DE" } },
+        );
+
+        let fixtures = Self {
+            anonymous_ip: temp_path(&format!("{tag}-anonymous-ip.mmdb")),
+            asn: temp_path(&format!("{tag}-asn.mmdb")),
+            city: temp_path(&format!("{tag}-city.mmdb")),
+        };
+        anonymous_ip.write(&fixtures.anonymous_ip);
+        asn.write(&fixtures.asn);
+        city.write(&fixtures.city);
+        fixtures
+    }
+
+    fn paths(&self) -> MmdbPaths {
+        MmdbPaths {
+            anonymous_ip: Some(self.anonymous_ip.clone()),
+            asn: Some(self.asn.clone()),
+            city: Some(self.city.clone()),
+        }
+    }
+}
+
+impl Drop for Fixtures {
+    fn drop(&mut self) {
+        for path in [&self.anonymous_ip, &self.asn, &self.city] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[test]
+fn maps_anonymous_flags_asn_and_country() {
+    let fixtures = Fixtures::generate("combined");
+    let db = IpDatabase::load_from_mmdb(&fixtures.paths()).unwrap();
+
+    let m = db.lookup("198.51.100.20".parse().unwrap()).unwrap();
+    assert_eq!(m.cidr.to_string(), "198.51.100.0/24");
+    assert_eq!(m.asn, 64500);
+    assert_eq!(&*m.provider, "Example VPN Hosting");
+    assert_eq!(m.country.as_deref(), Some("NL"));
+    assert_eq!(m.category, Some(Category::Vpn));
+    assert_eq!(&*m.source, fixtures.anonymous_ip.display().to_string());
+    assert!(m.is_vpn());
+
+    let tor = db.lookup("203.0.113.7".parse().unwrap()).unwrap();
+    assert_eq!(tor.category, Some(Category::Tor));
+    assert_eq!(tor.asn, 0);
+
+    let v6 = db.lookup("2001:db8:1::1".parse().unwrap()).unwrap();
+    assert_eq!(v6.cidr.to_string(), "2001:db8:1::/48");
+    // Public proxy outranks the broader hosting flag.
+    assert_eq!(v6.category, Some(Category::Proxy));
+}
+
+#[test]
+fn unflagged_address_reports_asn_without_vpn() {
+    let fixtures = Fixtures::generate("unflagged");
+    let db = IpDatabase::load_from_mmdb(&fixtures.paths()).unwrap();
+
+    let m = db.lookup("192.0.2.1".parse().unwrap()).unwrap();
+    assert_eq!(m.asn, 64501);
+    assert_eq!(m.country.as_deref(), Some("DE"));
+    assert_eq!(&*m.source, fixtures.asn.display().to_string());
+    assert!(!m.is_vpn());
+    assert!(!db.is_vpn_ip("192.0.2.1".parse().unwrap()));
+
+    assert!(db.lookup("100.64.0.1".parse().unwrap()).is_none());
+}
+
+#[test]
+fn anonymous_ip_database_alone_is_enough() {
+    let fixtures = Fixtures::generate("anon-only");
+    let db = IpDatabase::load_from_mmdb(&MmdbPaths {
+        anonymous_ip: Some(fixtures.anonymous_ip.clone()),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let m = db.lookup("198.51.100.20".parse().unwrap()).unwrap();
+    assert!(m.is_vpn());
+    assert_eq!(m.asn, 0);
+    assert_eq!(m.country, None);
+}
+
+#[test]
+fn requires_at_least_one_database() {
+    assert!(matches!(
+        IpDatabase::load_from_mmdb(&MmdbPaths::default()),
+        Err(GeoIpError::NoMmdbConfigured)
+    ));
+    assert!(matches!(
+        IpDatabase::load_from_mmdb(&MmdbPaths {
+            asn: Some(temp_path("missing.mmdb")),
+            ..Default::default()
+        }),
+        Err(GeoIpError::MmdbError(_))
+    ));
+}
+
+#[test]
+fn writer_round_trips_plain_values() {
+    let path = temp_path("values.mmdb");
+    let mut writer = MmdbWriter::new("Test");
+    writer.insert("10.0.0.0/8", &Value::from("ten"));
+    writer.write(&path);
+
+    let reader = maxminddb::Reader::open_readfile(&path).unwrap();
+    let (value, prefix_len): (&str, usize) =
+        reader.lookup_prefix("10.1.2.3".parse().unwrap()).unwrap();
+    assert_eq!((value, prefix_len), ("ten", 8));
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use common::{fixture_db, write};
+use geo_ip::{GeoIpError, IpDatabase, Verdict};
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
+
+const FEED: &str = "\
+cidr,asn,provider,category
+10.0.0.0/8,64500,Example VPN,vpn
+";
+
+const OVERRIDES: &str = "\
+cidr,verdict,reason,expires
+10.0.0.0/8,flag,whole range,
+10.1.0.0/16,allow,office network,
+10.1.2.0/24,flag,shared lab,2026-01-01T00:00:00Z
+198.51.100.7/32,flag,abuse report,
+";
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn time(s: &str) -> SystemTime {
+    humantime::parse_rfc3339(s).unwrap()
+}
+
+fn with_overrides(name: &str, overrides: &str) -> Result<IpDatabase, GeoIpError> {
+    let mut ip_db = fixture_db(&format!("{}.csv", name), FEED);
+    let list = write(&format!("{}-overrides.csv", name), overrides);
+    let loaded = ip_db.load_overrides(&list);
+    std::fs::remove_file(list).unwrap();
+    loaded.map(|_| ip_db)
+}
+
+#[test]
+fn most_specific_override_wins() {
+    let ip_db = with_overrides("overrides-specific", OVERRIDES).unwrap();
+    let before_expiry = time("2025-06-01T00:00:00Z");
+    let verdict = |addr: &str| {
+        ip_db
+            .check_override_at(ip(addr), before_expiry)
+            .map(|o| (o.cidr.to_string(), o.verdict))
+    };
+
+    assert_eq!(
+        verdict("10.200.0.1"),
+        Some(("10.0.0.0/8".into(), Verdict::Flag))
+    );
+    assert_eq!(
+        verdict("10.1.9.1"),
+        Some(("10.1.0.0/16".into(), Verdict::Allow))
+    );
+    assert_eq!(
+        verdict("10.1.2.3"),
+        Some(("10.1.2.0/24".into(), Verdict::Flag))
+    );
+    assert_eq!(verdict("192.0.2.1"), None);
+
+    let office = ip_db
+        .check_override_at(ip("10.1.9.1"), before_expiry)
+        .unwrap();
+    assert_eq!(office.reason.as_deref(), Some("office network"));
+    assert_eq!(office.expires, None);
+}
+
+#[test]
+fn expired_overrides_fall_back_to_the_next_covering_one() {
+    let ip_db = with_overrides("overrides-expiry", OVERRIDES).unwrap();
+    let expires = time("2026-01-01T00:00:00Z");
+
+    let before = ip_db
+        .check_override_at(ip("10.1.2.3"), expires - Duration::from_secs(1))
+        .unwrap();
+    assert_eq!(before.verdict, Verdict::Flag);
+    assert_eq!(before.expires, Some(expires));
+
+    let after = ip_db.check_override_at(ip("10.1.2.3"), expires).unwrap();
+    assert_eq!(after.cidr.to_string(), "10.1.0.0/16");
+    assert_eq!(after.verdict, Verdict::Allow);
+}
+
+#[test]
+fn allow_beats_a_flagged_range_and_flag_covers_unlisted_addresses() {
+    let ip_db = with_overrides(
+        "overrides-verdicts",
+        "cidr,verdict,reason,expires\n\
+         10.1.0.0/16,allow,office network,\n\
+         198.51.100.7/32,flag,abuse report,\n",
+    )
+    .unwrap();
+
+    // The range is still listed; the override only changes the verdict.
+    assert!(ip_db.lookup(ip("10.1.9.1")).is_some_and(|m| m.is_vpn()));
+    assert!(!ip_db.is_vpn_ip(ip("10.1.9.1")));
+    assert!(ip_db.is_vpn_ip(ip("10.2.0.1")));
+
+    assert!(ip_db.lookup(ip("198.51.100.7")).is_none());
+    assert!(ip_db.is_vpn_ip(ip("198.51.100.7")));
+    assert!(!ip_db.is_vpn_ip(ip("198.51.100.8")));
+}
+
+#[test]
+fn invalid_override_rows_are_rejected() {
+    let err = with_overrides(
+        "overrides-verdict",
+        "cidr,verdict,reason,expires\n10.0.0.0/8,block,,\n",
+    )
+    .unwrap_err();
+    assert!(matches!(err, GeoIpError::InvalidVerdict(ref v) if v == "block"));
+
+    let err = with_overrides(
+        "overrides-timestamp",
+        "cidr,verdict,reason,expires\n10.0.0.0/8,allow,,next week\n",
+    )
+    .unwrap_err();
+    assert!(matches!(err, GeoIpError::InvalidTimestamp(ref t) if t == "next week"));
+}
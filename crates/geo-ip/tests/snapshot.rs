@@ -0,0 +1,201 @@
+mod common;
+
+use common::temp_path;
+use geo_ip::{Category, GeoIpError, IpDatabase, LoadMode};
+
+const FEED: &str = "\
+cidr,asn,provider,category
+10.0.0.0/8,64500,Example VPN,vpn
+10.1.0.0/16,64501,Example Hosting,hosting
+2001:db8::/32,64502,Example Proxy,proxy
+";
+
+#[test]
+fn snapshot_round_trips_lookups_and_metadata() {
+    let feed = temp_path("snapshot-feed.csv");
+    let snapshot = temp_path("snapshot.bin");
+    std::fs::write(&feed, FEED).unwrap();
+
+    let csv_db = IpDatabase::load_from_csv(&feed).unwrap();
+    csv_db.write_snapshot(&snapshot).unwrap();
+
+    let (snapshot_db, report) = IpDatabase::load_from_file(&snapshot, LoadMode::Strict).unwrap();
+    assert_eq!(report.loaded, 3);
+    let metadata = snapshot_db.snapshot_metadata().unwrap();
+    assert_eq!(metadata.version, 5);
+    assert_eq!(metadata.sources, [feed.display().to_string().into()]);
+
+    for ip in ["10.1.2.3", "10.200.0.1", "2001:db8::1", "192.0.2.1"] {
+        let ip = ip.parse().unwrap();
+        assert_eq!(snapshot_db.lookup(ip), csv_db.lookup(ip), "{}", ip);
+    }
+    let hit = snapshot_db.lookup("2001:db8::1".parse().unwrap()).unwrap();
+    assert_eq!(hit.category, Some(Category::Proxy));
+
+    let (from_csv, _) = IpDatabase::load_from_file(&feed, LoadMode::Strict).unwrap();
+    assert!(from_csv.snapshot_metadata().is_none());
+
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(snapshot).unwrap();
+}
+
+#[test]
+fn corrupt_or_foreign_version_snapshots_are_rejected() {
+    let feed = temp_path("corrupt-feed.csv");
+    let snapshot = temp_path("corrupt.bin");
+    std::fs::write(&feed, FEED).unwrap();
+    IpDatabase::load_from_csv(&feed)
+        .unwrap()
+        .write_snapshot(&snapshot)
+        .unwrap();
+    let bytes = std::fs::read(&snapshot).unwrap();
+
+    let mut corrupt = bytes.clone();
+    *corrupt.last_mut().unwrap() ^= 0xff;
+    std::fs::write(&snapshot, &corrupt).unwrap();
+    let err = IpDatabase::load_snapshot(&snapshot).unwrap_err();
+    assert!(matches!(err, GeoIpError::InvalidSnapshot(ref reason) if reason.contains("checksum")));
+
+    let mut future = bytes;
+    future[8..12].copy_from_slice(&99u32.to_le_bytes());
+    std::fs::write(&snapshot, &future).unwrap();
+    let err = IpDatabase::load_snapshot(&snapshot).unwrap_err();
+    assert!(matches!(err, GeoIpError::UnsupportedSnapshotVersion(99)));
+
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(snapshot).unwrap();
+}
+
+#[test]
+fn replaced_duplicates_are_not_stored() {
+    let feed = temp_path("duplicates-feed.csv");
+    let snapshot = temp_path("duplicates.bin");
+    std::fs::write(
+        &feed,
+        "cidr,asn,provider,category\n\
+         10.0.0.0/8,64500,Example VPN,vpn\n\
+         192.0.2.0/24,64501,Example Proxy,proxy\n\
+         10.0.0.0/8,64500,Example VPN Renamed,vpn\n",
+    )
+    .unwrap();
+
+    let (csv_db, report) = IpDatabase::load_from_file(&feed, LoadMode::Strict).unwrap();
+    assert_eq!((report.loaded, report.duplicates), (3, 1));
+    csv_db.write_snapshot(&snapshot).unwrap();
+
+    let (snapshot_db, report) = IpDatabase::load_from_file(&snapshot, LoadMode::Strict).unwrap();
+    assert_eq!(report.loaded, 2);
+    for ip in ["10.1.2.3", "192.0.2.1"] {
+        let ip = ip.parse().unwrap();
+        assert_eq!(snapshot_db.lookup(ip), csv_db.lookup(ip), "{}", ip);
+    }
+    let hit = snapshot_db.lookup("10.1.2.3".parse().unwrap()).unwrap();
+    assert_eq!(&*hit.provider, "Example VPN Renamed");
+
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(snapshot).unwrap();
+}
+
+/// Fixes up the payload length and checksum after `bytes` was edited.
+fn reseal(mut bytes: Vec<u8>) -> Vec<u8> {
+    let payload_len = (bytes.len() - 24) as u64;
+    bytes[16..24].copy_from_slice(&payload_len.to_le_bytes());
+    let checksum = crc32fast::hash(&bytes[24..]);
+    bytes[12..16].copy_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn malformed_tries_are_rejected_despite_a_valid_checksum() {
+    let feed = temp_path("trie-feed.csv");
+    let snapshot = temp_path("trie.bin");
+    // One IPv4 network: the IPv4 arena is the root and a chain of eight
+    // nodes, the last holding entry 0, followed by an IPv6 arena that is
+    // just an empty root.
+    std::fs::write(
+        &feed,
+        "cidr,asn,provider,category\n10.0.0.0/8,64500,Example VPN,vpn\n",
+    )
+    .unwrap();
+    IpDatabase::load_from_csv(&feed)
+        .unwrap()
+        .write_snapshot(&snapshot)
+        .unwrap();
+    let bytes = std::fs::read(&snapshot).unwrap();
+    let ipv6_arena = bytes.len() - 16;
+    let last_ipv4_node = ipv6_arena - 16;
+
+    let rejected = |bytes: Vec<u8>| {
+        std::fs::write(&snapshot, reseal(bytes)).unwrap();
+        let err = IpDatabase::load_snapshot(&snapshot).unwrap_err();
+        assert!(
+            matches!(err, GeoIpError::InvalidSnapshot(ref reason) if reason == "malformed trie"),
+            "{}",
+            err
+        );
+    };
+
+    // The deepest node points back up the chain.
+    let mut cycle = bytes.clone();
+    cycle[last_ipv4_node..last_ipv4_node + 4].copy_from_slice(&1u32.to_le_bytes());
+    rejected(cycle);
+
+    // The entry is moved from 10.0.0.0/8 to the IPv6 root.
+    let mut misplaced = bytes;
+    misplaced[last_ipv4_node + 8..last_ipv4_node + 12].copy_from_slice(&0u32.to_le_bytes());
+    misplaced.truncate(last_ipv4_node + 12);
+    for value in [1u32, 0, 0, 1, 0] {
+        misplaced.extend_from_slice(&value.to_le_bytes());
+    }
+    rejected(misplaced);
+
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(snapshot).unwrap();
+}
+
+#[test]
+fn out_of_range_timestamps_are_rejected_despite_a_valid_checksum() {
+    let feed = temp_path("timestamp-feed.csv");
+    let snapshot = temp_path("timestamp.bin");
+    std::fs::write(
+        &feed,
+        "cidr,asn,provider,category\n10.0.0.0/8,64500,Example VPN,vpn\n",
+    )
+    .unwrap();
+    IpDatabase::load_from_csv(&feed)
+        .unwrap()
+        .write_snapshot(&snapshot)
+        .unwrap();
+    let bytes = std::fs::read(&snapshot).unwrap();
+    let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+
+    let rejected = |bytes: Vec<u8>| {
+        std::fs::write(&snapshot, reseal(bytes)).unwrap();
+        let err = IpDatabase::load_snapshot(&snapshot).unwrap_err();
+        assert!(
+            matches!(err, GeoIpError::InvalidSnapshot(ref reason) if reason == "timestamp out of range"),
+            "{}",
+            err
+        );
+    };
+
+    // The payload starts with the creation time.
+    let mut created = bytes.clone();
+    created[24..32].copy_from_slice(&u64::MAX.to_le_bytes());
+    rejected(created);
+
+    // Skip the string table and entry count to reach the only entry, whose
+    // last_seen follows 39 bytes of fixed fields and first_seen. u64::MAX
+    // itself means unknown.
+    let mut at = 32 + 4;
+    for _ in 0..u32_at(32) {
+        at += 4 + u32_at(at);
+    }
+    let last_seen = at + 4 + 39 + 8;
+    let mut entry = bytes.clone();
+    entry[last_seen..last_seen + 8].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+    rejected(entry);
+
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(snapshot).unwrap();
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::temp_path;
+use geo_ip::{Category, IpDatabase, ListFormat, LoadMode, SourceOptions};
+
+#[test]
+fn lookup_reports_every_matching_source() {
+    let vendor = temp_path("sources-vendor.csv");
+    let community = temp_path("sources-community.netset");
+    std::fs::write(
+        &vendor,
+        "cidr,asn,provider,category\n\
+         10.0.0.0/8,64500,Example VPN,vpn\n\
+         10.1.0.0/16,64500,Example VPN,\n\
+         10.1.0.0/16,64500,Example VPN Renamed,\n",
+    )
+    .unwrap();
+    std::fs::write(&community, "10.1.0.0/16\n10.1.2.0/24\n").unwrap();
+
+    let mut ip_db = IpDatabase::default();
+    let vendor_report = ip_db
+        .merge_csv(
+            &vendor,
+            &SourceOptions {
+                category: Category::Hosting,
+                weight: 0.9,
+            },
+            LoadMode::Strict,
+        )
+        .unwrap();
+    let community_report = ip_db
+        .merge_list(
+            &community,
+            ListFormat::Cidr,
+            &SourceOptions {
+                category: Category::Proxy,
+                weight: 0.5,
+            },
+            LoadMode::Strict,
+        )
+        .unwrap();
+    // The same network from another feed is corroboration, not a duplicate.
+    assert_eq!(vendor_report.duplicates, 1);
+    assert_eq!(community_report.duplicates, 0);
+
+    let hit = ip_db.lookup("10.1.2.3".parse().unwrap()).unwrap();
+    assert_eq!(hit.cidr.to_string(), "10.1.2.0/24");
+    assert_eq!(hit.category, Some(Category::Proxy));
+    let sources: Vec<_> = hit
+        .sources
+        .iter()
+        .map(|s| (s.cidr.to_string(), s.category, s.weight))
+        .collect();
+    assert_eq!(
+        sources,
+        [
+            ("10.1.2.0/24".to_string(), Some(Category::Proxy), 0.5),
+            ("10.1.0.0/16".to_string(), Some(Category::Hosting), 0.9),
+        ]
+    );
+
+    // The feed merged last is primary for a network both list; rows without
+    // a category take their source's default.
+    let hit = ip_db.lookup("10.1.200.1".parse().unwrap()).unwrap();
+    assert_eq!(hit.source, community.display().to_string().into());
+    assert_eq!(hit.sources.len(), 2);
+    assert_eq!(hit.sources[1].category, Some(Category::Hosting));
+
+    let hit = ip_db.lookup("10.200.0.1".parse().unwrap()).unwrap();
+    assert_eq!(hit.category, Some(Category::Vpn));
+    assert_eq!(hit.sources.len(), 1);
+
+    let snapshot = temp_path("sources.bin");
+    ip_db.write_snapshot(&snapshot).unwrap();
+    let restored = IpDatabase::load_snapshot(&snapshot).unwrap();
+    for ip in ["10.1.2.3", "10.1.200.1", "10.200.0.1"] {
+        let ip = ip.parse().unwrap();
+        assert_eq!(restored.lookup(ip), ip_db.lookup(ip));
+    }
+
+    for path in [vendor, community, snapshot] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn asn_zero_and_none_rows_are_listed_but_not_flagged() {
+    let feed = temp_path("sources-unflagged.csv");
+    std::fs::write(
+        &feed,
+        "cidr,asn,provider,category\n\
+         10.0.0.0/8,64500,Example VPN,\n\
+         10.1.0.0/16,0,Example ISP,\n\
+         10.2.0.0/16,64501,Example Carrier,none\n\
+         10.3.0.0/16,0,Example Proxy,proxy\n",
+    )
+    .unwrap();
+
+    let mut ip_db = IpDatabase::default();
+    ip_db
+        .merge_csv(&feed, &SourceOptions::default(), LoadMode::Strict)
+        .unwrap();
+
+    let category = |ip: &str| ip_db.lookup(ip.parse().unwrap()).map(|m| m.category);
+    assert_eq!(category("10.200.0.1"), Some(Some(Category::Vpn)));
+    // Carve-outs still match, as the more specific range, without a flag.
+    assert_eq!(category("10.1.0.1"), Some(None));
+    assert_eq!(category("10.2.0.1"), Some(None));
+    assert_eq!(category("10.3.0.1"), Some(Some(Category::Proxy)));
+    assert!(!ip_db.is_vpn_ip("10.1.0.1".parse().unwrap()));
+
+    let snapshot = temp_path("sources-unflagged.bin");
+    ip_db.write_snapshot(&snapshot).unwrap();
+    let restored = IpDatabase::load_snapshot(&snapshot).unwrap();
+    for ip in ["10.1.0.1", "10.2.0.1", "10.3.0.1"] {
+        let ip = ip.parse().unwrap();
+        assert_eq!(restored.lookup(ip), ip_db.lookup(ip));
+    }
+
+    std::fs::remove_file(feed).unwrap();
+    std::fs::remove_file(snapshot).unwrap();
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::{temp_path, write};
+use geo_ip::{Category, GeoIpError, IpDatabase};
+use std::time::{Duration, SystemTime};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+const EXIT_ADDRESSES: &str = "\
+ExitNode 0011BD2485AD45D984EC4159C88FC066E5E3300E
+Published 2026-10-01 10:00:00
+LastStatus 2026-10-01 11:00:00
+ExitAddress 192.0.2.10 2026-10-01 10:30:00
+ExitAddress 2001:db8::10 2026-10-01 10:30:00
+ExitNode 0091174DE56EA1C5DE1D3D0B14B9A3EFAF8ED3AF
+Published 2026-10-02 10:00:00
+LastStatus 2026-10-02 11:00:00
+ExitAddress 192.0.2.20 2026-10-02 10:30:00
+";
+
+fn time(s: &str) -> SystemTime {
+    humantime::parse_rfc3339_weak(s).unwrap()
+}
+
+fn merge(name: &str, contents: &str, max_age: Option<Duration>) -> (IpDatabase, usize) {
+    let path = write(name, contents);
+    let mut ip_db = IpDatabase::default();
+    let merged = ip_db.merge_tor_exit_list(&path, max_age);
+    std::fs::remove_file(path).unwrap();
+    (ip_db, merged.unwrap())
+}
+
+#[test]
+fn bulk_list_addresses_are_tor_and_never_expire() {
+    let (ip_db, merged) = merge(
+        "tor-bulk.txt",
+        "# exit list\n192.0.2.1\n\n2001:db8::1\n",
+        Some(HOUR),
+    );
+    assert_eq!(merged, 2);
+
+    let hit = ip_db.lookup("192.0.2.1".parse().unwrap()).unwrap();
+    assert_eq!(hit.category, Some(Category::Tor));
+    assert_eq!(hit.cidr.network_length(), 32);
+    assert_eq!(hit.published, None);
+    let far_future = SystemTime::now() + 1000 * 24 * HOUR;
+    assert!(ip_db
+        .lookup_at("2001:db8::1".parse().unwrap(), far_future)
+        .is_some());
+    assert!(ip_db.lookup("192.0.2.2".parse().unwrap()).is_none());
+}
+
+#[test]
+fn exit_addresses_take_the_published_time_of_their_exit_node() {
+    let (ip_db, merged) = merge("tor-detailed.txt", EXIT_ADDRESSES, None);
+    assert_eq!(merged, 3);
+
+    let published = |ip: &str| ip_db.lookup(ip.parse().unwrap()).and_then(|m| m.published);
+    assert_eq!(published("192.0.2.10"), Some(time("2026-10-01 10:00:00")));
+    assert_eq!(published("2001:db8::10"), Some(time("2026-10-01 10:00:00")));
+    assert_eq!(published("192.0.2.20"), Some(time("2026-10-02 10:00:00")));
+}
+
+#[test]
+fn exits_expire_at_lookup_time_once_older_than_max_age() {
+    let (ip_db, _) = merge("tor-expiry.txt", EXIT_ADDRESSES, Some(24 * HOUR));
+    let lookup = |ip: &str, now: &str| ip_db.lookup_at(ip.parse().unwrap(), time(now));
+
+    assert!(lookup("192.0.2.10", "2026-10-02 09:59:59").is_some());
+    assert!(lookup("192.0.2.10", "2026-10-02 10:00:00").is_none());
+    assert!(lookup("2001:db8::10", "2026-10-02 10:00:00").is_none());
+    // The second exit node was published a day later.
+    assert!(lookup("192.0.2.20", "2026-10-02 10:00:00").is_some());
+    assert!(lookup("192.0.2.20", "2026-10-03 10:00:00").is_none());
+}
+
+#[test]
+fn malformed_lines_are_reported_with_their_line_number() {
+    let path = temp_path("tor-malformed.txt");
+    let mut ip_db = IpDatabase::default();
+
+    std::fs::write(&path, "192.0.2.1\nnot-an-address\n").unwrap();
+    let err = ip_db.merge_tor_exit_list(&path, None).unwrap_err();
+    assert!(matches!(
+        err,
+        GeoIpError::InvalidTorExitList { line: 2, .. }
+    ));
+
+    std::fs::write(&path, "ExitNode ABC\nPublished yesterday\n").unwrap();
+    let err = ip_db.merge_tor_exit_list(&path, None).unwrap_err();
+    assert!(matches!(
+        err,
+        GeoIpError::InvalidTorExitList { line: 2, .. }
+    ));
+
+    std::fs::write(
+        &path,
+        "ExitNode ABC\nExitAddress bogus 2026-10-01 10:30:00\n",
+    )
+    .unwrap();
+    let err = ip_db.merge_tor_exit_list(&path, None).unwrap_err();
+    assert!(matches!(
+        err,
+        GeoIpError::InvalidTorExitList { line: 2, .. }
+    ));
+
+    std::fs::remove_file(path).unwrap();
+}
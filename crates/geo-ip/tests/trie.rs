@@ -0,0 +1,116 @@
+use cidr_utils::cidr::IpCidr;
+use geo_ip::IpTrie;
+use std::net::IpAddr;
+
+fn cidr(s: &str) -> IpCidr {
+    s.parse().unwrap()
+}
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn trie(networks: &[&'static str]) -> IpTrie<&'static str> {
+    let mut trie = IpTrie::new();
+    for network in networks {
+        trie.insert(&cidr(network), *network);
+    }
+    trie
+}
+
+#[test]
+fn most_specific_nested_prefix_wins_regardless_of_insert_order() {
+    let networks = ["10.0.0.0/8", "10.1.0.0/16", "10.1.2.0/24"];
+    let mut reversed = networks;
+    reversed.reverse();
+
+    for trie in [trie(&networks), trie(&reversed)] {
+        assert_eq!(trie.longest_match(ip("10.1.2.3")), Some(&"10.1.2.0/24"));
+        assert_eq!(trie.longest_match(ip("10.1.3.3")), Some(&"10.1.0.0/16"));
+        assert_eq!(trie.longest_match(ip("10.2.0.1")), Some(&"10.0.0.0/8"));
+        assert_eq!(trie.longest_match(ip("11.0.0.1")), None);
+        assert_eq!(
+            trie.matches(ip("10.1.2.3")),
+            [
+                (&"10.0.0.0/8", 8),
+                (&"10.1.0.0/16", 16),
+                (&"10.1.2.0/24", 24)
+            ]
+        );
+    }
+}
+
+#[test]
+fn zero_length_prefix_matches_everything_in_its_family() {
+    let trie = trie(&["0.0.0.0/0", "192.0.2.0/24"]);
+    assert_eq!(
+        trie.longest_match_with_len(ip("203.0.113.1")),
+        Some((&"0.0.0.0/0", 0))
+    );
+    assert_eq!(
+        trie.longest_match_with_len(ip("192.0.2.1")),
+        Some((&"192.0.2.0/24", 24))
+    );
+    assert_eq!(trie.longest_match(ip("2001:db8::1")), None);
+}
+
+#[test]
+fn host_routes_match_only_their_own_address() {
+    let trie = trie(&[
+        "192.0.2.0/24",
+        "192.0.2.7/32",
+        "2001:db8::/32",
+        "2001:db8::7/128",
+    ]);
+    assert_eq!(
+        trie.longest_match_with_len(ip("192.0.2.7")),
+        Some((&"192.0.2.7/32", 32))
+    );
+    assert_eq!(trie.longest_match(ip("192.0.2.6")), Some(&"192.0.2.0/24"));
+    assert_eq!(
+        trie.longest_match_with_len(ip("2001:db8::7")),
+        Some((&"2001:db8::7/128", 128))
+    );
+    assert_eq!(
+        trie.longest_match(ip("2001:db8::6")),
+        Some(&"2001:db8::/32")
+    );
+}
+
+#[test]
+fn ipv6_prefixes_nest_like_ipv4() {
+    let trie = trie(&["2001:db8::/32", "2001:db8:1::/48", "2001:db8:1:2::/64"]);
+    assert_eq!(
+        trie.longest_match(ip("2001:db8:1:2::1")),
+        Some(&"2001:db8:1:2::/64")
+    );
+    assert_eq!(
+        trie.longest_match(ip("2001:db8:1:3::1")),
+        Some(&"2001:db8:1::/48")
+    );
+    assert_eq!(
+        trie.longest_match(ip("2001:db8:2::1")),
+        Some(&"2001:db8::/32")
+    );
+    assert_eq!(trie.longest_match(ip("2001:db9::1")), None);
+}
+
+#[test]
+fn ipv4_and_ipv6_are_kept_apart() {
+    // ::/0 and 0.0.0.0/0 share the all-zero key but not a family.
+    let trie = trie(&["::/0", "10.0.0.0/8"]);
+    assert_eq!(trie.longest_match(ip("10.0.0.1")), Some(&"10.0.0.0/8"));
+    assert_eq!(trie.longest_match(ip("192.0.2.1")), None);
+    assert_eq!(trie.longest_match(ip("::a00:1")), Some(&"::/0"));
+    assert_eq!(trie.longest_match(ip("::ffff:10.0.0.1")), Some(&"::/0"));
+    assert_eq!(trie.len(), 2);
+}
+
+#[test]
+fn reinserting_a_network_replaces_its_value() {
+    let mut trie = IpTrie::new();
+    assert_eq!(trie.insert(&cidr("10.0.0.0/8"), 1), None);
+    assert_eq!(trie.insert(&cidr("10.0.0.0/8"), 2), Some(1));
+    assert_eq!(trie.len(), 1);
+    assert_eq!(trie.longest_match(ip("10.9.9.9")), Some(&2));
+}
@@ -3,6 +3,9 @@
 pub struct CheckIpRequest {
     #[prost(string, tag = "1")]
     pub ip: ::prost::alloc::string::String,
+    /// Token from IssueLeakProbe whose resolvers to compare with `ip`.
+    #[prost(string, tag = "2")]
+    pub leak_token: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CheckIpResponse {
@@ -12,6 +15,142 @@ pub struct CheckIpResponse {
     pub is_vpn: bool,
     #[prost(float, tag = "3")]
     pub score: f32,
+    /// Set when an operator override decided the verdict.
+    #[prost(message, optional, tag = "4")]
+    pub operator_override: ::core::option::Option<OperatorOverride>,
+    /// Where the address is located; unset when unknown.
+    #[prost(message, optional, tag = "5")]
+    pub location: ::core::option::Option<Location>,
+    /// Address the matching database range contains, and how it relates to
+    /// `ip`: "direct", "ipv4_mapped", "ipv4_compatible", "6to4", "teredo" or
+    /// "nat64". Empty when no range matched.
+    #[prost(string, tag = "6")]
+    pub matched_address: ::prost::alloc::string::String,
+    #[prost(string, tag = "7")]
+    pub address_form: ::prost::alloc::string::String,
+    /// PTR hostname rules the address's reverse DNS names matched.
+    #[prost(message, repeated, tag = "8")]
+    pub ptr_rule_matches: ::prost::alloc::vec::Vec<PtrRuleMatch>,
+    /// Forward-confirmed reverse DNS: "confirmed", "mismatch" or "no_ptr".
+    /// Empty when DNS was not consulted.
+    #[prost(string, tag = "9")]
+    pub fcrdns: ::prost::alloc::string::String,
+    /// Reverse zone of the address; unset when it publishes no SOA.
+    #[prost(message, optional, tag = "10")]
+    pub reverse_zone: ::core::option::Option<ReverseZone>,
+    /// Zone rules matching the reverse zone's name servers or SOA names.
+    #[prost(message, repeated, tag = "11")]
+    pub zone_rule_matches: ::prost::alloc::vec::Vec<PtrRuleMatch>,
+    /// Resolvers that looked up the leak probe of `leak_token`. Empty when no
+    /// token was given, it expired, or the probe was not resolved yet.
+    #[prost(message, repeated, tag = "12")]
+    pub leak_resolvers: ::prost::alloc::vec::Vec<LeakResolver>,
+    /// DNSBL zones listing the address.
+    #[prost(message, repeated, tag = "13")]
+    pub dnsbl_listings: ::prost::alloc::vec::Vec<DnsblListing>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct IssueLeakProbeRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LeakProbe {
+    #[prost(string, tag = "1")]
+    pub token: ::prost::alloc::string::String,
+    /// Name for the client to resolve, e.g. by loading a resource from it.
+    #[prost(string, tag = "2")]
+    pub hostname: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LeakResolver {
+    #[prost(string, tag = "1")]
+    pub ip: ::prost::alloc::string::String,
+    /// What the IP database says about the resolver; empty when unlisted.
+    #[prost(string, tag = "2")]
+    pub provider: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub category: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "4")]
+    pub asn: u32,
+    #[prost(string, tag = "5")]
+    pub country: ::prost::alloc::string::String,
+    /// Same ASN as the client when both are known, otherwise same /24 or /48.
+    #[prost(bool, tag = "6")]
+    pub same_network: bool,
+    /// Same country as the client; unset when either cannot be located.
+    #[prost(bool, optional, tag = "7")]
+    pub same_country: ::core::option::Option<bool>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OperatorOverride {
+    #[prost(string, tag = "1")]
+    pub cidr: ::prost::alloc::string::String,
+    /// "allow" or "flag".
+    #[prost(string, tag = "2")]
+    pub verdict: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub reason: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Location {
+    /// ISO 3166-1 alpha-2 code; empty when unknown.
+    #[prost(string, tag = "1")]
+    pub country: ::prost::alloc::string::String,
+    /// Subdivision such as a state or province; empty when unknown.
+    #[prost(string, tag = "2")]
+    pub region: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub coordinates: ::core::option::Option<Coordinates>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct Coordinates {
+    #[prost(double, tag = "1")]
+    pub latitude: f64,
+    #[prost(double, tag = "2")]
+    pub longitude: f64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PtrRuleMatch {
+    /// First name the rule matched: a PTR name or, for zone rules, a name
+    /// server or SOA name of the reverse zone.
+    #[prost(string, tag = "1")]
+    pub hostname: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub pattern: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub category: ::prost::alloc::string::String,
+    #[prost(float, tag = "4")]
+    pub weight: f32,
+    /// Score the rule added to the DNS score.
+    #[prost(float, tag = "5")]
+    pub contribution: f32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReverseZone {
+    /// Zone apex, e.g. "2.0.192.in-addr.arpa".
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// SOA MNAME and RNAME.
+    #[prost(string, tag = "2")]
+    pub primary_ns: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub contact: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "4")]
+    pub name_servers: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DnsblListing {
+    #[prost(string, tag = "1")]
+    pub zone: ::prost::alloc::string::String,
+    /// A record the zone returned, e.g. "127.0.0.4".
+    #[prost(string, tag = "2")]
+    pub code: ::prost::alloc::string::String,
+    /// From the zone's return-code table; "listed" for codes it does not map.
+    #[prost(string, tag = "3")]
+    pub category: ::prost::alloc::string::String,
+    #[prost(float, tag = "4")]
+    pub weight: f32,
+    /// The zone's TXT explanation, if any.
+    #[prost(string, repeated, tag = "5")]
+    pub text: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// Generated client implementations.
 pub mod vpn_detector_service_client {
@@ -120,6 +259,26 @@ pub mod vpn_detector_service_client {
             ));
             self.inner.unary(req, path, codec).await
         }
+        /// Issues a DNS leak probe name for the client to resolve; pass its token
+        /// to CheckIp. Fails when no probe zone is configured.
+        pub async fn issue_leak_probe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::IssueLeakProbeRequest>,
+        ) -> std::result::Result<tonic::Response<super::LeakProbe>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/vpn_detector.VpnDetectorService/IssueLeakProbe",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new(
+                "vpn_detector.VpnDetectorService",
+                "IssueLeakProbe",
+            ));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -139,6 +298,12 @@ pub mod vpn_detector_service_server {
             &self,
             request: tonic::Request<super::CheckIpRequest>,
         ) -> std::result::Result<tonic::Response<super::CheckIpResponse>, tonic::Status>;
+        /// Issues a DNS leak probe name for the client to resolve; pass its token
+        /// to CheckIp. Fails when no probe zone is configured.
+        async fn issue_leak_probe(
+            &self,
+            request: tonic::Request<super::IssueLeakProbeRequest>,
+        ) -> std::result::Result<tonic::Response<super::LeakProbe>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct VpnDetectorServiceServer<T> {
@@ -252,6 +417,48 @@ pub mod vpn_detector_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/vpn_detector.VpnDetectorService/IssueLeakProbe" => {
+                    #[allow(non_camel_case_types)]
+                    struct IssueLeakProbeSvc<T: VpnDetectorService>(pub Arc<T>);
+                    impl<T: VpnDetectorService>
+                        tonic::server::UnaryService<super::IssueLeakProbeRequest>
+                        for IssueLeakProbeSvc<T>
+                    {
+                        type Response = super::LeakProbe;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::IssueLeakProbeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as VpnDetectorService>::issue_leak_probe(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = IssueLeakProbeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     let mut response = http::Response::new(empty_body());
                     let headers = response.headers_mut();
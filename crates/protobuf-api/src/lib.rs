@@ -17,6 +17,16 @@ impl From<DetectionResult> for vpn_detector::CheckIpResponse {
             ip: item.ip,
             is_vpn: item.is_vpn,
             score: item.score,
+            operator_override: None,
+            location: None,
+            matched_address: String::new(),
+            address_form: String::new(),
+            ptr_rule_matches: Vec::new(),
+            fcrdns: String::new(),
+            reverse_zone: None,
+            zone_rule_matches: Vec::new(),
+            leak_resolvers: Vec::new(),
+            dnsbl_listings: Vec::new(),
         }
     }
 }